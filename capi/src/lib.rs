@@ -0,0 +1,5 @@
+//! C library of the GMT geometric transformations
+//!
+//! Builds the [`geotrans::ffi`] C ABI, declared in `include/geotrans.h`, as a cdylib and a staticlib.
+
+pub use geotrans::ffi::*;
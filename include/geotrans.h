@@ -0,0 +1,70 @@
+/*
+ * GMT geometric transformations C API
+ *
+ * The geotrans library is built as a cdylib and a staticlib by the geotrans-capi
+ * workspace member:
+ *   cargo build --release -p geotrans-capi
+ * then link against libgeotrans.so (or libgeotrans.a).
+ *
+ * Point and vector buffers are arrays of 3*n_point doubles [x0,y0,z0,x1,y1,z1,...]
+ * in meters, transformed in place; GEOTRANS_INVALID_LENGTH is returned if 3*n_point
+ * doubles overflow the address space.
+ */
+
+#ifndef GEOTRANS_H
+#define GEOTRANS_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by all the functions */
+typedef enum {
+    GEOTRANS_OK = 0,
+    GEOTRANS_NULL_POINTER = 1,
+    GEOTRANS_INVALID_MIRROR = 2,
+    GEOTRANS_INVALID_SEGMENT = 3,
+    GEOTRANS_INVALID_LENGTH = 4,
+    GEOTRANS_PANIC = 5
+} geotrans_status;
+
+/* Mirror selection */
+#define GEOTRANS_M1 1
+#define GEOTRANS_M2 2
+
+/* Opaque segment handle */
+typedef struct geotrans_segment geotrans_segment;
+
+/* Creates the segment `id` in [1,7] of `mirror`; release it with geotrans_segment_free.
+   On error, `*segment` is set to NULL */
+geotrans_status geotrans_segment_new(int mirror, int id, geotrans_segment **segment);
+/* Releases a segment, NULL is ignored */
+void geotrans_segment_free(geotrans_segment *segment);
+/* Writes the segment origin coordinates in the OSS into xyz[3] */
+geotrans_status geotrans_segment_translation(const geotrans_segment *segment, double *xyz);
+/* Writes the segment rotation quaternion [w,x,y,z] into q[4] */
+geotrans_status geotrans_segment_rotation(const geotrans_segment *segment, double *q);
+
+/* Segment points coordinates to the OSS */
+geotrans_status geotrans_to(const geotrans_segment *segment, double *xyz, size_t n_point);
+/* OSS points coordinates to the segment */
+geotrans_status geotrans_fro(const geotrans_segment *segment, double *xyz, size_t n_point);
+/* Segment vectors to the OSS */
+geotrans_status geotrans_vtov(const geotrans_segment *segment, double *xyz, size_t n_point);
+/* OSS vectors to the segment */
+geotrans_status geotrans_vfrov(const geotrans_segment *segment, double *xyz, size_t n_point);
+
+/* Writes the 42 segment rigid body motions [Tx,Ty,Tz,Rx,Ry,Rz]x7 of a mirror tip-tilt [rd]
+ * into rbm; len must be 42 */
+geotrans_status geotrans_tiptilt_to_rbm(int mirror, double tip, double tilt, double *rbm, size_t len);
+
+/* Returns a static description of a status code */
+const char *geotrans_status_message(int status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GEOTRANS_H */
@@ -0,0 +1,222 @@
+//! Segment clear apertures
+//!
+//! The clear aperture of a segment is a circle, or an annulus for a center segment with
+//! a central obscuration, in the x-y plane of the segment coordinate system.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+use crate::{Error, Gmt, Mirror, Segment, SegmentTrait, Transform, Vector};
+
+/// Segment clear aperture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aperture {
+    /// Outer diameter \[m\]
+    pub(crate) outer_diameter: f64,
+    /// Central obscuration diameter \[m\]
+    pub(crate) inner_diameter: f64,
+}
+impl Aperture {
+    /// Creates a new annular aperture
+    ///
+    /// The outer diameter must be positive and the central obscuration diameter in the range \[0,outer\[
+    pub fn new(outer_diameter: f64, inner_diameter: f64) -> Result<Self, Error> {
+        if !(outer_diameter.is_finite() && outer_diameter > 0f64) {
+            return Err(Error::Prescription(
+                "the aperture outer diameter must be finite and positive",
+            ));
+        }
+        if !(inner_diameter >= 0f64 && inner_diameter < outer_diameter) {
+            return Err(Error::Prescription(
+                "the aperture inner diameter must be positive and smaller than the outer diameter",
+            ));
+        }
+        Ok(Self {
+            outer_diameter,
+            inner_diameter,
+        })
+    }
+    /// Returns the outer radius
+    pub fn outer_radius(&self) -> f64 {
+        0.5 * self.outer_diameter
+    }
+    /// Returns the central obscuration radius
+    pub fn inner_radius(&self) -> f64 {
+        0.5 * self.inner_diameter
+    }
+    /// Checks if the aperture has a central obscuration
+    pub fn is_annular(&self) -> bool {
+        self.inner_diameter > 0f64
+    }
+    /// Returns the clear aperture area
+    pub fn area(&self) -> f64 {
+        PI * (self.outer_radius().powi(2) - self.inner_radius().powi(2))
+    }
+    /// Checks if the point `(x,y)` is inside the aperture
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let r = x.hypot(y);
+        r <= self.outer_radius() && (!self.is_annular() || r >= self.inner_radius())
+    }
+}
+
+/// Segment aperture outline
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct Outline {
+    /// Outer edge polygon
+    pub outer: Vec<Vector>,
+    /// Central obscuration polygon
+    pub inner: Option<Vec<Vector>>,
+}
+#[cfg(feature = "alloc")]
+impl Outline {
+    /// Returns the outline transformed from the segment coordinates into the OSS
+    pub fn to<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        Self {
+            outer: self
+                .outer
+                .into_iter()
+                .map(|v| v.to(segment.clone()))
+                .collect(),
+            inner: self
+                .inner
+                .map(|inner| inner.into_iter().map(|v| v.to(segment.clone())).collect()),
+        }
+    }
+}
+
+impl<M: Gmt> Segment<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    #[cfg(feature = "alloc")]
+    fn circle(&self, radius: f64, n: usize) -> Vec<Vector> {
+        (0..n)
+            .map(|i| {
+                let (s, c) = (2. * PI * i as f64 / n as f64).sin_cos();
+                let (x, y) = (radius * c, radius * s);
+                Vector::from([x, y, self.surface(x, y)])
+            })
+            .collect()
+    }
+    /// Returns the `n` vertices polygons of the segment aperture edges in the segment coordinates
+    ///
+    /// The vertices lie on the segment surface
+    #[cfg(feature = "alloc")]
+    pub fn outline(&self, n: usize) -> Outline {
+        let aperture = self.aperture();
+        Outline {
+            outer: self.circle(aperture.outer_radius(), n),
+            inner: if aperture.is_annular() {
+                Some(self.circle(aperture.inner_radius(), n))
+            } else {
+                None
+            },
+        }
+    }
+    /// Returns the `n` vertices polygons of the segment aperture edges in the OSS
+    #[cfg(feature = "alloc")]
+    pub fn outline_oss(&self, n: usize) -> Outline {
+        self.outline(n).to(self.clone())
+    }
+    /// Checks if the point `(x,y)` in the segment coordinates is inside the segment aperture
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.aperture().contains(x, y)
+    }
+    /// Checks if the point `(x,y)` in the OSS is inside the segment aperture
+    ///
+    /// The point is projected along the OSS z-axis onto the mirror parent conic
+    pub fn contains_oss(&self, x: f64, y: f64) -> bool {
+        let v = Vector::from([x, y, self.parent_surface(x, y)]).fro(self.clone());
+        self.contains(v[0], v[1])
+    }
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the segment which aperture contains the point `(x,y)` in the OSS
+    pub fn segment_at(x: f64, y: f64) -> Option<Segment<M>> {
+        Self::segments().find(|segment| segment.contains_oss(x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn m1_outline_on_conic() {
+        for sid in 1..=7 {
+            let segment = Segment::<M1>::new(sid).unwrap();
+            let outline = segment.outline_oss(36);
+            for v in outline.outer.iter().chain(outline.inner.iter().flatten()) {
+                assert!((v[2] - segment.parent_surface(v[0], v[1])).abs() < 1e-9);
+            }
+            assert_eq!(outline.inner.is_some(), sid == 7);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn m2_outline_on_conic() {
+        for sid in 1..=7 {
+            let segment = Segment::<M2>::new(sid).unwrap();
+            let outline = segment.outline(36);
+            assert!(outline.outer.iter().all(|v| segment.contains(v[0], v[1])));
+            for v in outline.to(segment.clone()).outer {
+                assert!((v[2] - segment.parent_surface(v[0], v[1])).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Aperture::new(8.365, 3.2).is_ok());
+        for (outer, inner) in [
+            (-1., 0.),
+            (0., 0.),
+            (f64::NAN, 0.),
+            (f64::INFINITY, 0.),
+            (1., -0.5),
+            (1., 1.),
+            (1., 2.),
+            (1., f64::NAN),
+        ] {
+            assert!(matches!(
+                Aperture::new(outer, inner),
+                Err(Error::Prescription(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn m1_segment_at() {
+        for sid in 1..=6 {
+            let t = Segment::<M1>::new(sid).unwrap().translation();
+            assert_eq!(Mirror::<M1>::segment_at(t[0], t[1]).unwrap().id(), sid);
+        }
+        assert_eq!(Mirror::<M1>::segment_at(2., 0.).unwrap().id(), 7);
+        assert!(Mirror::<M1>::segment_at(0., 0.).is_none());
+        assert!(Mirror::<M1>::segment_at(20., 0.).is_none());
+    }
+
+    #[test]
+    fn m2_segment_at() {
+        for sid in 1..=7 {
+            let t = Segment::<M2>::new(sid).unwrap().translation();
+            assert_eq!(Mirror::<M2>::segment_at(t[0], t[1]).unwrap().id(), sid);
+        }
+    }
+}
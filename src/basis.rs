@@ -0,0 +1,378 @@
+//! Segment modal bases
+//!
+//! The modes are polynomials in the segment coordinates normalized by the segment
+//! outer radius, orthonormalized (unit RMS) over the segment samples.
+//! For the circular segments the Zernike modes are the Noll normalized Zernike polynomials,
+//! for the center segment of M1 they become the annular Zernike polynomials.
+//! The modes are linear combinations of the polynomials and can be evaluated anywhere
+//! on the segment with [`ModalBasis::evaluate`].
+
+use crate::{linalg, Error, Gmt, Samples, Sampling, Segment, SegmentTrait};
+
+/// Polynomial families
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Polynomials {
+    /// The first `n` Zernike polynomials in Noll order
+    Zernike(usize),
+    /// The products of Legendre polynomials `Pi(x)Pj(y)` up to degree `i+j=n`
+    Legendre(usize),
+}
+impl Polynomials {
+    /// Returns the number of polynomials
+    fn len(&self) -> usize {
+        match *self {
+            Polynomials::Zernike(n_mode) => n_mode,
+            Polynomials::Legendre(degree) => (degree + 1) * (degree + 2) / 2,
+        }
+    }
+    /// Returns the polynomial values at the normalized coordinates `(x,y)`
+    fn eval(&self, x: f64, y: f64) -> Vec<f64> {
+        match *self {
+            Polynomials::Zernike(n_mode) => {
+                let (r, o) = (x.hypot(y), y.atan2(x));
+                (1..=n_mode).map(|j| zernike(j, r, o)).collect()
+            }
+            Polynomials::Legendre(degree) => (0..=degree)
+                .flat_map(|d| (0..=d).map(move |j| (d - j, j)))
+                .map(|(i, j)| legendre(i, x) * legendre(j, y))
+                .collect(),
+        }
+    }
+}
+
+/// Returns the radial and azimuthal orders `(n,m)` of the Zernike polynomial with Noll index `j`
+///
+/// A positive (negative) azimuthal order is a cosine (sine) mode.
+/// The Noll indices start at 1: `None` is returned for `j=0`
+pub fn noll_to_nm(j: usize) -> Option<(usize, i32)> {
+    (j > 0).then(|| noll(j))
+}
+/// Returns the orders `(n,m)` of the Noll index `j>0`
+fn noll(j: usize) -> (usize, i32) {
+    let n = ((-1. + (8. * (j - 1) as f64 + 1.).sqrt()) / 2.) as usize;
+    let p = j - n * (n + 1) / 2;
+    let k = (n % 2) as i32;
+    let m = ((p as i32 + k) / 2) * 2 - k;
+    if m != 0 && j % 2 == 1 {
+        (n, -m)
+    } else {
+        (n, m)
+    }
+}
+/// Noll normalized Zernike polynomial `j` at the polar coordinates `(r,o)`
+fn zernike(j: usize, r: f64, o: f64) -> f64 {
+    let (n, m) = noll(j);
+    let ma = m.unsigned_abs() as usize;
+    let radial = (0..=(n - ma) / 2).fold(0f64, |s, k| {
+        let c = factorial(n - k)
+            / (factorial(k) * factorial((n + ma) / 2 - k) * factorial((n - ma) / 2 - k));
+        s + if k % 2 == 0 { c } else { -c } * r.powi((n - 2 * k) as i32)
+    });
+    let norm = (n as f64 + 1.).sqrt();
+    match m {
+        0 => norm * radial,
+        m if m > 0 => 2f64.sqrt() * norm * radial * (ma as f64 * o).cos(),
+        _ => 2f64.sqrt() * norm * radial * (ma as f64 * o).sin(),
+    }
+}
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1f64, |p, i| p * i as f64)
+}
+/// Legendre polynomial of degree `n`
+fn legendre(n: usize, x: f64) -> f64 {
+    let (mut p0, mut p1) = (1f64, x);
+    if n == 0 {
+        return p0;
+    }
+    for k in 1..n {
+        let k = k as f64;
+        let p2 = ((2. * k + 1.) * x * p1 - k * p0) / (k + 1.);
+        p0 = p1;
+        p1 = p2;
+    }
+    p1
+}
+
+/// Orthonormal modal basis of a segment
+#[derive(Debug, Clone)]
+pub struct ModalBasis {
+    polynomials: Polynomials,
+    /// Segment samples
+    samples: Samples,
+    /// Segment outer radius
+    radius: f64,
+    /// Modes values at the samples, one mode per row
+    modes: Vec<Vec<f64>>,
+    /// Modes coefficients on the polynomials, one mode per row
+    coefficients: Vec<Vec<f64>>,
+    /// Segment surface slopes `(dz/dx,dz/dy)` at the samples
+    slopes: Vec<(f64, f64)>,
+}
+impl ModalBasis {
+    /// Creates the modal basis of the segment from the samples of the segment surface
+    ///
+    /// Polynomials that are linearly dependent on the lower order ones over the samples are discarded,
+    /// an error is returned if there are fewer samples than polynomials
+    pub fn new<M>(
+        segment: &Segment<M>,
+        polynomials: Polynomials,
+        sampling: &Sampling,
+    ) -> Result<Self, Error>
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let samples = segment.sample(sampling);
+        let n_raw = polynomials.len();
+        if samples.is_empty() || samples.len() < n_raw {
+            return Err(Error::Undersampled(samples.len(), n_raw));
+        }
+        let radius = segment.aperture().outer_radius();
+        let n_sample = samples.len() as f64;
+        let raw: Vec<Vec<f64>> = samples
+            .local
+            .iter()
+            .map(|v| polynomials.eval(v[0] / radius, v[1] / radius))
+            .collect();
+        // modified Gram-Schmidt, carrying the coefficients of the modes on the polynomials
+        let mut modes: Vec<Vec<f64>> = vec![];
+        let mut coefficients: Vec<Vec<f64>> = vec![];
+        for i in 0..n_raw {
+            let mut mode: Vec<f64> = raw.iter().map(|p| p[i]).collect();
+            let mut coefficient = vec![0f64; n_raw];
+            coefficient[i] = 1f64;
+            let norm0 = (linalg::dot(&mode, &mode) / n_sample).sqrt();
+            for (other, other_coefficient) in modes.iter().zip(&coefficients) {
+                let c = linalg::dot(&mode, other) / n_sample;
+                mode.iter_mut().zip(other).for_each(|(m, o)| *m -= c * o);
+                coefficient
+                    .iter_mut()
+                    .zip(other_coefficient)
+                    .for_each(|(m, o)| *m -= c * o);
+            }
+            let norm = (linalg::dot(&mode, &mode) / n_sample).sqrt();
+            if norm > 1e-9 * norm0 {
+                mode.iter_mut().for_each(|m| *m /= norm);
+                coefficient.iter_mut().for_each(|m| *m /= norm);
+                modes.push(mode);
+                coefficients.push(coefficient);
+            }
+        }
+        // surface slopes from central differences
+        let h = 1e-4 * radius;
+        let slopes = samples
+            .local
+            .iter()
+            .map(|v| {
+                let (x, y) = (v[0], v[1]);
+                (
+                    (segment.surface(x + h, y) - segment.surface(x - h, y)) / (2. * h),
+                    (segment.surface(x, y + h) - segment.surface(x, y - h)) / (2. * h),
+                )
+            })
+            .collect();
+        Ok(Self {
+            polynomials,
+            samples,
+            radius,
+            modes,
+            coefficients,
+            slopes,
+        })
+    }
+    /// Returns the polynomial family
+    pub fn polynomials(&self) -> Polynomials {
+        self.polynomials
+    }
+    /// Returns the segment samples
+    pub fn samples(&self) -> &Samples {
+        &self.samples
+    }
+    /// Returns the number of modes
+    pub fn n_mode(&self) -> usize {
+        self.modes.len()
+    }
+    /// Returns the modes values at the samples, one mode per row
+    pub fn modes(&self) -> &[Vec<f64>] {
+        &self.modes
+    }
+    /// Returns the modes values at the segment coordinates `(x,y)`
+    pub fn evaluate(&self, x: f64, y: f64) -> Vec<f64> {
+        let raw = self.polynomials.eval(x / self.radius, y / self.radius);
+        self.coefficients
+            .iter()
+            .map(|c| linalg::dot(c, &raw))
+            .collect()
+    }
+    /// Returns the coefficients of the least-squares fit of the modes to the `surface` values at the samples
+    pub fn fit(&self, surface: &[f64]) -> Result<Vec<f64>, Error> {
+        if surface.len() != self.samples.len() {
+            return Err(Error::SurfaceLength(surface.len(), self.samples.len()));
+        }
+        let n_sample = self.samples.len() as f64;
+        Ok(self
+            .modes
+            .iter()
+            .map(|mode| linalg::dot(mode, surface) / n_sample)
+            .collect())
+    }
+    /// Returns the surface values at the samples for the modes `coefficients`
+    pub fn synthesize(&self, coefficients: &[f64]) -> Result<Vec<f64>, Error> {
+        if coefficients.len() != self.modes.len() {
+            return Err(Error::MatrixLength(coefficients.len(), self.modes.len()));
+        }
+        let mut surface = vec![0f64; self.samples.len()];
+        for (mode, c) in self.modes.iter().zip(coefficients) {
+            surface.iter_mut().zip(mode).for_each(|(s, m)| *s += c * m);
+        }
+        Ok(surface)
+    }
+    /// Returns the surface values at the samples of the segment rigid body motions
+    /// `[Tx,Ty,Tz,Rx,Ry,Rz]` (small angles approximation)
+    ///
+    /// The lateral motions `Tx`,`Ty` and the clocking `Rz` change the surface through its slopes
+    /// `(dz/dx,dz/dy)`: `-(Tx dz/dx + Ty dz/dy) + Rz (y dz/dx - x dz/dy)`
+    pub fn rbm_surface(&self, rbm: &[f64]) -> Result<Vec<f64>, Error> {
+        if rbm.len() != 6 {
+            return Err(Error::RbmLength(rbm.len(), 6));
+        }
+        Ok(self
+            .samples
+            .local
+            .iter()
+            .zip(&self.slopes)
+            .map(|(v, (zx, zy))| {
+                let (x, y) = (v[0], v[1]);
+                rbm[2] + rbm[3] * y - rbm[4] * x - (rbm[0] * zx + rbm[1] * zy)
+                    + rbm[5] * (y * zx - x * zy)
+            })
+            .collect())
+    }
+    /// Returns the mode coefficients of the segment rigid body motions `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub fn rbm_to_coefficients(&self, rbm: &[f64]) -> Result<Vec<f64>, Error> {
+        self.fit(&self.rbm_surface(rbm)?)
+    }
+    /// Returns the segment outer radius used to normalize the coordinates
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[test]
+    fn noll() {
+        let nm: Vec<_> = (1..=7).filter_map(noll_to_nm).collect();
+        assert_eq!(
+            nm,
+            vec![(0, 0), (1, 1), (1, -1), (2, 0), (2, -2), (2, 2), (3, -1)]
+        );
+    }
+
+    #[test]
+    fn noll_zero() {
+        assert!(noll_to_nm(0).is_none());
+    }
+
+    #[test]
+    fn zernike_circular() {
+        let segment = Segment::<M1>::new(1).unwrap();
+        let basis =
+            ModalBasis::new(&segment, Polynomials::Zernike(15), &Sampling::Grid(101)).unwrap();
+        assert_eq!(basis.n_mode(), 15);
+        // the orthonormalized focus is close to the Noll normalized Zernike focus
+        let focus: Vec<f64> = basis
+            .samples()
+            .local
+            .iter()
+            .map(|v| zernike(4, v[0].hypot(v[1]) / basis.radius(), 0.))
+            .collect();
+        let c = basis.fit(&focus).unwrap();
+        assert!((c[3] - 1.).abs() < 1e-2);
+        let coefs: Vec<f64> = (1..=15).map(|i| i as f64 * 1e-3).collect();
+        let fit = basis.fit(&basis.synthesize(&coefs).unwrap()).unwrap();
+        assert!(fit.iter().zip(&coefs).all(|(a, b)| (a - b).abs() < 1e-12));
+        assert!(matches!(
+            basis.synthesize(&coefs[..14]),
+            Err(Error::MatrixLength(14, 15))
+        ));
+    }
+
+    #[test]
+    fn zernike_annular() {
+        let segment = Segment::<M1>::new(7).unwrap();
+        let basis =
+            ModalBasis::new(&segment, Polynomials::Zernike(10), &Sampling::Grid(64)).unwrap();
+        let n = basis.samples().len() as f64;
+        for (i, a) in basis.modes().iter().enumerate() {
+            for (j, b) in basis.modes().iter().enumerate() {
+                let d = linalg::dot(a, b) / n;
+                assert!((d - if i == j { 1. } else { 0. }).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate() {
+        let segment = Segment::<M1>::new(7).unwrap();
+        let basis = ModalBasis::new(
+            &segment,
+            Polynomials::Legendre(4),
+            &Sampling::Fibonacci(500),
+        )
+        .unwrap();
+        for (k, v) in basis.samples().local.iter().enumerate().step_by(50) {
+            let values = basis.evaluate(v[0], v[1]);
+            for (mode, value) in basis.modes().iter().zip(&values) {
+                assert!((mode[k] - value).abs() < 1e-9);
+            }
+        }
+        assert_eq!(basis.evaluate(0.5, -1.).len(), basis.n_mode());
+    }
+
+    #[test]
+    fn undersampled() {
+        let segment = Segment::<M2>::new(1).unwrap();
+        assert!(matches!(
+            ModalBasis::new(&segment, Polynomials::Zernike(3), &Sampling::Fibonacci(0)),
+            Err(Error::Undersampled(0, 3))
+        ));
+        assert!(matches!(
+            ModalBasis::new(&segment, Polynomials::Legendre(2), &Sampling::Fibonacci(5)),
+            Err(Error::Undersampled(5, 6))
+        ));
+    }
+
+    #[test]
+    fn rbm_coefficients() {
+        let segment = Segment::<M2>::new(2).unwrap();
+        let basis =
+            ModalBasis::new(&segment, Polynomials::Legendre(3), &Sampling::Grid(41)).unwrap();
+        assert_eq!(basis.n_mode(), 10);
+        let c = basis
+            .rbm_to_coefficients(&[0., 0., 1e-6, 0., 0., 0.])
+            .unwrap();
+        assert!((c[0] - 1e-6).abs() < 1e-15);
+        assert!(c[1..].iter().all(|c| c.abs() < 1e-15));
+        // lateral motion and clocking against the displaced surface
+        let (t, r) = (1e-6, 1e-6);
+        let dz = basis.rbm_surface(&[t, 0., 0., 0., 0., r]).unwrap();
+        for (v, dz) in basis.samples().local.iter().zip(&dz) {
+            let (x, y) = (v[0], v[1]);
+            let z = segment.surface(x - t + r * y, y - r * x) - v[2];
+            assert!((z - dz).abs() < 1e-12);
+        }
+        let c = basis
+            .rbm_to_coefficients(&[1e-6, 0., 0., 0., 0., 0.])
+            .unwrap();
+        assert!(c[1].abs() > 1e-9);
+        let c = basis
+            .rbm_to_coefficients(&[0., 0., 0., 1e-6, 0., 0.])
+            .unwrap();
+        assert!(c[2].abs() > 1e-7 && c[0].abs() < 1e-12 && c[1].abs() < 1e-12);
+        assert!(basis.rbm_to_coefficients(&[0.; 3]).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! GMT geometric transformations command line interface
+
+use std::{
+    convert::TryFrom,
+    error::Error,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use geotrans::{
+    Frame, Gmt, Mirror, PointCloud, Segment, SegmentId, SegmentTrait, Transform, Vector, M1, M2,
+};
+
+#[derive(Parser)]
+#[command(author, version, about = "GMT geometric transformations")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transforms the coordinates of segment points into the OSS
+    To(Conversion),
+    /// Transforms the coordinates of OSS points into a segment
+    Fro(Conversion),
+    /// Transforms segment vectors into the OSS
+    Vtov(Conversion),
+    /// Transforms OSS vectors into a segment
+    Vfrov(Conversion),
+    /// Prints the segment geometry table
+    Geometry {
+        #[arg(short, long, value_enum, default_value_t = MirrorArg::M1)]
+        mirror: MirrorArg,
+    },
+    /// Prints the segment rigid body motions of a mirror tip-tilt
+    Tiptilt {
+        #[arg(short, long, value_enum, default_value_t = MirrorArg::M1)]
+        mirror: MirrorArg,
+        /// Tip angle (rotation around the OSS x-axis)
+        #[arg(allow_negative_numbers = true)]
+        tip: f64,
+        /// Tilt angle (rotation around the OSS y-axis)
+        #[arg(allow_negative_numbers = true)]
+        tilt: f64,
+        /// Unit of the tip-tilt and segment rotation angles
+        #[arg(short, long, value_enum, default_value_t = AngleUnit::Arcsec)]
+        angle_unit: AngleUnit,
+        /// Unit of the segment translations
+        #[arg(short, long, value_enum, default_value_t = LengthUnit::Um)]
+        length_unit: LengthUnit,
+    },
+}
+
+#[derive(Args)]
+struct Conversion {
+    #[arg(short, long, value_enum)]
+    mirror: MirrorArg,
+    /// Segment # id in the range [1,7]
+    #[arg(short, long)]
+    segment: i32,
+    /// Point coordinates `x y z` [m], if omitted the points are read from the input file or from stdin
+    #[arg(allow_negative_numbers = true, num_args = 3, conflicts_with = "input")]
+    point: Option<Vec<f64>>,
+    /// CSV or whitespace separated file with one point per line and an optional header,
+    /// a frame header must be the segment of `to` and `vtov` or the OSS otherwise
+    #[arg(short, long)]
+    input: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MirrorArg {
+    M1,
+    M2,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    To,
+    Fro,
+    Vtov,
+    Vfrov,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AngleUnit {
+    Rad,
+    Deg,
+    Arcsec,
+    Mas,
+}
+impl AngleUnit {
+    /// Returns the angle unit in radians
+    fn radians(&self) -> f64 {
+        match self {
+            AngleUnit::Rad => 1f64,
+            AngleUnit::Deg => 1f64.to_radians(),
+            AngleUnit::Arcsec => 1f64.to_radians() / 3600.,
+            AngleUnit::Mas => 1f64.to_radians() / 3600e3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LengthUnit {
+    M,
+    Mm,
+    Um,
+    Nm,
+}
+impl LengthUnit {
+    /// Returns the length unit in meters
+    fn meters(&self) -> f64 {
+        match self {
+            LengthUnit::M => 1f64,
+            LengthUnit::Mm => 1e-3,
+            LengthUnit::Um => 1e-6,
+            LengthUnit::Nm => 1e-9,
+        }
+    }
+}
+
+fn convert<M: Gmt, W: Write>(
+    out: &mut W,
+    operation: Operation,
+    sid: SegmentId,
+    points: Vec<Vector>,
+) -> Result<(), Box<dyn Error>>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let segment = Segment::<M>::from_id(sid);
+    for point in points {
+        let v = match operation {
+            Operation::To => point.to(segment.clone()),
+            Operation::Fro => point.fro(segment.clone()),
+            Operation::Vtov => point.vtov(segment.clone()),
+            Operation::Vfrov => point.vfrov(segment.clone()),
+        };
+        writeln!(out, "{},{},{}", v[0], v[1], v[2])?;
+    }
+    Ok(())
+}
+
+fn geometry<M: Gmt, W: Write>(out: &mut W) -> io::Result<()>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    writeln!(
+        out,
+        "id,x[m],y[m],z[m],roll[deg],pitch[deg],yaw[deg],outer diameter[m],inner diameter[m]"
+    )?;
+    for segment in Mirror::<M>::segments() {
+        let t = segment.translation();
+        let (r, p, y) = segment
+            .rotation()
+            .map_or((0., 0., 0.), |q| q.euler_angles());
+        let aperture = segment.aperture();
+        writeln!(
+            out,
+            "{},{:.6},{:.6},{:.6},{:.6},{:.6},{:.6},{},{}",
+            segment.id(),
+            t[0],
+            t[1],
+            t[2],
+            r.to_degrees(),
+            p.to_degrees(),
+            y.to_degrees(),
+            2. * aperture.outer_radius(),
+            2. * aperture.inner_radius()
+        )?;
+    }
+    Ok(())
+}
+
+fn tiptilt<M: Gmt, W: Write>(
+    out: &mut W,
+    tip: f64,
+    tilt: f64,
+    angle_unit: AngleUnit,
+    length_unit: LengthUnit,
+) -> io::Result<()>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let (a, l) = (angle_unit.radians(), length_unit.meters());
+    let rbm = Mirror::<M>::tiptilt_2_rigidbodymotions((tip * a, tilt * a));
+    writeln!(out, "id,Tx,Ty,Tz,Rx,Ry,Rz")?;
+    for (i, rbm) in rbm.chunks(6).enumerate() {
+        writeln!(
+            out,
+            "{},{:e},{:e},{:e},{:e},{:e},{:e}",
+            i + 1,
+            rbm[0] / l,
+            rbm[1] / l,
+            rbm[2] / l,
+            rbm[3] / a,
+            rbm[4] / a,
+            rbm[5] / a
+        )?;
+    }
+    Ok(())
+}
+
+/// Runs the command line `cli`, writing the results to `out`
+fn run<W: Write>(cli: Cli, out: &mut W) -> Result<(), Box<dyn Error>> {
+    let (operation, conversion) = match cli.command {
+        Command::To(c) => (Operation::To, c),
+        Command::Fro(c) => (Operation::Fro, c),
+        Command::Vtov(c) => (Operation::Vtov, c),
+        Command::Vfrov(c) => (Operation::Vfrov, c),
+        Command::Geometry { mirror } => {
+            match mirror {
+                MirrorArg::M1 => geometry::<M1, _>(out)?,
+                MirrorArg::M2 => geometry::<M2, _>(out)?,
+            }
+            return Ok(());
+        }
+        Command::Tiptilt {
+            mirror,
+            tip,
+            tilt,
+            angle_unit,
+            length_unit,
+        } => {
+            match mirror {
+                MirrorArg::M1 => tiptilt::<M1, _>(out, tip, tilt, angle_unit, length_unit)?,
+                MirrorArg::M2 => tiptilt::<M2, _>(out, tip, tilt, angle_unit, length_unit)?,
+            }
+            return Ok(());
+        }
+    };
+    // the points are given in the segment frame for `to` and `vtov` and in the OSS otherwise,
+    // a point cloud without frame header is read in that frame
+    let sid = SegmentId::try_from(conversion.segment)?;
+    let frame = match (operation, conversion.mirror) {
+        (Operation::Fro | Operation::Vfrov, _) => Frame::Oss,
+        (_, MirrorArg::M1) => Frame::M1(sid),
+        (_, MirrorArg::M2) => Frame::M2(sid),
+    };
+    let cloud = match (conversion.point, conversion.input) {
+        (Some(xyz), _) => PointCloud::new(frame, vec![Vector::try_from(xyz)?]),
+        (None, Some(path)) => PointCloud::from_file(path)?,
+        (None, None) => PointCloud::read(io::stdin().lock())?,
+    };
+    match cloud.frame() {
+        Some(header) if header != frame => {
+            return Err(format!(
+                "the points frame {} does not match the {} input frame",
+                header, frame
+            )
+            .into())
+        }
+        _ => (),
+    }
+    let points = cloud.points().to_vec();
+    match conversion.mirror {
+        MirrorArg::M1 => convert::<M1, _>(out, operation, sid, points),
+        MirrorArg::M2 => convert::<M2, _>(out, operation, sid, points),
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli, &mut io::stdout().lock()) {
+        eprintln!("geotrans: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geotrans(args: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+        let cli = Cli::try_parse_from(std::iter::once("geotrans").chain(args.iter().cloned()))?;
+        let mut out = vec![];
+        run(cli, &mut out)?;
+        Ok(String::from_utf8(out)?.lines().map(String::from).collect())
+    }
+
+    fn parse(line: &str) -> Vector {
+        let xyz: Vec<f64> = line.split(',').map(|x| x.parse().unwrap()).collect();
+        Vector::try_from(xyz).unwrap()
+    }
+
+    #[test]
+    fn to_fro() {
+        let lines =
+            geotrans(&["to", "--mirror", "m1", "--segment", "1", "0.1", "-0.1", "0"]).unwrap();
+        assert_eq!(lines.len(), 1);
+        let v = parse(&lines[0]);
+        let segment = Segment::<M1>::new(1).unwrap();
+        assert!((v.clone() - Vector::from([0.1, -0.1, 0.]).to(segment)).norm() < 1e-12);
+        let [x, y, z]: [f64; 3] = v.into();
+        let (x, y, z) = (x.to_string(), y.to_string(), z.to_string());
+        let lines = geotrans(&["fro", "-m", "m1", "-s", "1", &x, &y, &z]).unwrap();
+        assert!((parse(&lines[0]) - Vector::from([0.1, -0.1, 0.])).norm() < 1e-12);
+    }
+
+    /// Writes `data` to a file unique to the test `name` and the test process
+    fn input(name: &str, data: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("geotrans_{}_{}.csv", name, std::process::id()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn input_file() {
+        let path = input("input_file", "x,y,z\n0,0,0\n1,2,3\n");
+        let lines =
+            geotrans(&["vtov", "-m", "m2", "-s", "7", "-i", path.to_str().unwrap()]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+        let segment = Segment::<M2>::new(7).unwrap();
+        assert!((parse(&lines[1]) - Vector::from([1., 2., 3.]).vtov(segment)).norm() < 1e-12);
+    }
+
+    #[test]
+    fn input_frame() {
+        let path = input("input_frame", "# frame: M1S3\nx,y,z\n1,2,3\n");
+        let path = path.to_str().unwrap();
+        let lines = geotrans(&["to", "-m", "m1", "-s", "3", "-i", path]).unwrap();
+        let segment = Segment::<M1>::new(3).unwrap();
+        assert!((parse(&lines[0]) - Vector::from([1., 2., 3.]).to(segment)).norm() < 1e-12);
+        for args in [["to", "-m", "m1", "-s", "1"], ["to", "-m", "m2", "-s", "3"]] {
+            let e = geotrans(&[&args[..], &["-i", path]].concat()).unwrap_err();
+            assert!(e.to_string().contains("M1S3"), "{}", e);
+        }
+        assert!(geotrans(&["fro", "-m", "m1", "-s", "3", "-i", path]).is_err());
+        std::fs::remove_file(path).unwrap();
+        let path = input("input_frame_oss", "# frame: OSS\nx,y,z\n1,2,3\n");
+        let path = path.to_str().unwrap();
+        let e = geotrans(&["to", "-m", "m1", "-s", "3", "-i", path]).unwrap_err();
+        assert!(e.to_string().contains("OSS"), "{}", e);
+        assert!(geotrans(&["fro", "-m", "m1", "-s", "3", "-i", path]).is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tables() {
+        assert_eq!(geotrans(&["geometry", "--mirror", "m2"]).unwrap().len(), 8);
+        let lines =
+            geotrans(&["tiptilt", "-a", "rad", "-l", "m", "0.000001", "-0.0000025"]).unwrap();
+        assert_eq!(lines.len(), 8);
+        let rbm = Mirror::<M1>::tiptilt_2_rigidbodymotions((1e-6, -2.5e-6));
+        for (line, rbm) in lines.iter().skip(1).zip(rbm.chunks(6)) {
+            let values: Vec<f64> = line
+                .split(',')
+                .skip(1)
+                .map(|x| x.parse().unwrap())
+                .collect();
+            assert!(values.iter().zip(rbm).all(|(a, b)| (a - b).abs() < 1e-15));
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let e = geotrans(&["to", "-m", "m1", "-s", "8", "0", "0", "0"]).unwrap_err();
+        assert_eq!(e.to_string(), "The segment id 8 is not in the range [1,7]");
+        assert!(geotrans(&["to", "-m", "m1", "-s", "1", "0", "0"]).is_err());
+        assert!(geotrans(&["to", "-m", "m3", "-s", "1", "0", "0", "0"]).is_err());
+        let e = geotrans(&[
+            "fro",
+            "-m",
+            "m1",
+            "-s",
+            "1",
+            "-i",
+            "/nonexistent/points.csv",
+        ])
+        .unwrap_err();
+        assert!(!e.to_string().is_empty());
+        let path = input("errors", "x,y,z\n1,2,3\n");
+        let path = path.to_str().unwrap();
+        let e = geotrans(&["to", "-m", "m1", "-s", "1", "-i", path, "0", "0", "0"]).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert!(e.to_string().contains("cannot be used with"), "{}", e);
+    }
+}
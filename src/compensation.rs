@@ -0,0 +1,374 @@
+//! M1/M2 alignment and M2 compensation of the M1 misalignments
+//!
+//! The optical state of the telescope is computed by tracing the rays of an on-axis star
+//! through the M1 and M2 parent conics, displaced by the mirror global rigid body motions.
+//! The global rigid body motions are given in the OSS with the rotations about the center
+//! segment origin of each mirror, as in [`Mirror::global_2_rigidbodymotions`].
+//!
+//! The transverse ray aberrations in the nominal focal plane are fitted with the gradient of
+//! the wavefront `W = p·u + D ρ² + (C·u) ρ² + astigmatism`, `u` being the normalized pupil coordinates,
+//! giving the image displacement `p` (pointing), the defocus `D` and the coma `C`.
+//! The [`Metrics`] are the changes of these coefficients with respect to the aligned telescope.
+
+use crate::{
+    linalg, Conic, Error, Gmt, Mirror, Quaternion, Rbm, Segment, SegmentId, SegmentTrait, Vector,
+    M1, M2,
+};
+
+/// Radii of the pupil rings \[m\]
+const RINGS: [f64; 4] = [3., 6., 9., 12.];
+/// Number of rays per pupil ring
+const N_AZIMUTH: usize = 12;
+/// Finite difference step of the M2 sensitivities \[m,rd\]
+const STEP: f64 = 1e-6;
+/// Norm of the compensated metrics the Newton iterations must reach
+const TOLERANCE: f64 = 1e-12;
+
+/// Ray origin and direction
+type Ray = (Vector, Vector);
+
+/// Parent conic of a mirror displaced by its global rigid body motions
+struct Surface {
+    vertex: Vector,
+    conic: Conic,
+    translation: Vector,
+    q: Quaternion,
+}
+impl Surface {
+    fn new<M: Gmt>(motion: &Rbm) -> Self
+    where
+        Segment<M>: SegmentTrait,
+    {
+        let segment = Segment::<M>::from_id(SegmentId::CENTER);
+        Self {
+            vertex: segment.translation(),
+            conic: segment.conic().clone(),
+            translation: Vector::from(motion.translation),
+            q: motion.quaternion(),
+        }
+    }
+    /// Returns the intersection point and the unit normal of the ray from `p` along `d`
+    /// with the surface in the OSS
+    fn intersect(&self, p: &Vector, d: &Vector) -> Option<(Vector, Vector)> {
+        let qc = self.q.complex_conjugate();
+        let p = (&qc * (p.clone() - &self.vertex - &self.translation) * &self.q).vector();
+        let d = (&qc * d.clone() * &self.q).vector();
+        // x² + y² - 2Rz + (1+k)z² = 0
+        let (r, e) = (self.conic.radius, 1. + self.conic.constant);
+        let a = d[0] * d[0] + d[1] * d[1] + e * d[2] * d[2];
+        let b = 2. * (p[0] * d[0] + p[1] * d[1] - r * d[2] + e * p[2] * d[2]);
+        let c = p[0] * p[0] + p[1] * p[1] - 2. * r * p[2] + e * p[2] * p[2];
+        let disc = b * b - 4. * a * c;
+        if disc < 0. {
+            return None;
+        }
+        let h = -0.5 * (b + b.signum() * disc.sqrt());
+        // the root closest to the vertex in front of the ray
+        let s = [h / a, c / h]
+            .iter()
+            .copied()
+            .filter(|s| s.is_finite() && *s > 0.)
+            .min_by(|s, t| (p[2] + s * d[2]).abs().total_cmp(&(p[2] + t * d[2]).abs()))?;
+        let x = p + &d * s;
+        let n = Vector::from([x[0], x[1], e * x[2] - r]);
+        let n = &n / n.norm();
+        Some((
+            (&self.q * x * &qc).vector() + &self.vertex + &self.translation,
+            (&self.q * n * &qc).vector(),
+        ))
+    }
+    /// Reflects the ray from `p` along `d` on the surface
+    fn reflect(&self, p: &Vector, d: &Vector) -> Option<Ray> {
+        let (x, n) = self.intersect(p, d)?;
+        let r = d.clone() - &n * (2. * d.dot(&n));
+        Some((x, r))
+    }
+}
+
+/// Returns the rays `(p,d)` after the reflections on M1 and M2 with the normalized pupil coordinates
+fn trace(m1: &Rbm, m2: &Rbm) -> Vec<([f64; 2], Option<Ray>)> {
+    let (m1, m2) = (Surface::new::<M1>(m1), Surface::new::<M2>(m2));
+    let r_max = RINGS[RINGS.len() - 1];
+    RINGS
+        .iter()
+        .flat_map(|&r| {
+            (0..N_AZIMUTH).map(move |i| {
+                let o = 2. * core::f64::consts::PI * i as f64 / N_AZIMUTH as f64;
+                (r * o.cos(), r * o.sin())
+            })
+        })
+        .map(|(x, y)| {
+            let ray = m1
+                .reflect(&Vector::from([x, y, 100.]), &Vector::from([0., 0., -1.]))
+                .and_then(|(p, d)| m2.reflect(&p, &d));
+            ([x / r_max, y / r_max], ray)
+        })
+        .collect()
+}
+
+/// Returns the height of the nominal focal plane in the OSS
+fn focal_plane() -> f64 {
+    trace(&Rbm::default(), &Rbm::default())
+        .into_iter()
+        .find_map(|(_, ray)| ray)
+        .map_or(f64::NAN, |(p, d)| p[2] - p[0] * d[2] / d[0])
+}
+
+/// Aligned telescope focal plane and wavefront coefficients
+struct Nominal {
+    /// Height of the focal plane in the OSS
+    z: f64,
+    /// Wavefront coefficients in the focal plane
+    c0: [f64; 7],
+}
+impl Nominal {
+    /// Traces the aligned telescope
+    fn new() -> Self {
+        let z = focal_plane();
+        let c0 = coefficients(&Rbm::default(), &Rbm::default(), z);
+        Self { z, c0 }
+    }
+}
+
+/// Returns the wavefront coefficients `[px,py,D,Cx,Cy,A1,A2]` fitted to the transverse ray aberrations
+fn coefficients(m1: &Rbm, m2: &Rbm, z: f64) -> [f64; 7] {
+    let mut normal = [0f64; 49];
+    let mut rhs = [0f64; 7];
+    for ([u, v], ray) in trace(m1, m2) {
+        let (ex, ey) = ray.map_or((f64::NAN, f64::NAN), |(p, d)| {
+            let s = (z - p[2]) / d[2];
+            (p[0] + s * d[0], p[1] + s * d[1])
+        });
+        let rows = [
+            (
+                [
+                    1.,
+                    0.,
+                    2. * u,
+                    3. * u * u + v * v,
+                    2. * u * v,
+                    2. * u,
+                    2. * v,
+                ],
+                ex,
+            ),
+            (
+                [
+                    0.,
+                    1.,
+                    2. * v,
+                    2. * u * v,
+                    u * u + 3. * v * v,
+                    -2. * v,
+                    2. * u,
+                ],
+                ey,
+            ),
+        ];
+        for (row, e) in rows {
+            for i in 0..7 {
+                rhs[i] += row[i] * e;
+                for j in 0..7 {
+                    normal[i * 7 + j] += row[i] * row[j];
+                }
+            }
+        }
+    }
+    let mut c = [f64::NAN; 7];
+    if let Some(x) = linalg::solve(&normal, &rhs, 7) {
+        c.copy_from_slice(&x);
+    }
+    c
+}
+
+/// Optical metrics of the telescope with respect to the aligned telescope
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Metrics {
+    /// Image displacement in the focal plane `[x,y]` \[m\]
+    pub pointing: [f64; 2],
+    /// Defocus transverse aberration coefficient \[m\]
+    pub focus: f64,
+    /// Coma transverse aberration coefficients `[x,y]` \[m\]
+    pub coma: [f64; 2],
+}
+impl Metrics {
+    /// Returns the metrics as `[px,py,D,Cx,Cy]`
+    pub fn to_array(&self) -> [f64; 5] {
+        let Metrics {
+            pointing: [px, py],
+            focus,
+            coma: [cx, cy],
+        } = *self;
+        [px, py, focus, cx, cy]
+    }
+}
+
+/// Objectives of the M2 compensation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    /// Keeps the pointing and the focus with the M2 piston and tip-tilt `[Tz,Rx,Ry]`
+    PointingFocus,
+    /// Keeps the pointing, the focus and zero coma with the M2 `[Tx,Ty,Tz,Rx,Ry]`
+    ComaFree,
+}
+impl Objective {
+    /// Returns the M2 degrees of freedom and the metrics of the objective
+    fn indices(&self) -> (&'static [usize], &'static [usize]) {
+        match self {
+            Objective::PointingFocus => (&[2, 3, 4], &[0, 1, 2]),
+            Objective::ComaFree => (&[0, 1, 2, 3, 4], &[0, 1, 2, 3, 4]),
+        }
+    }
+}
+
+/// Global rigid body motions of M1 and M2
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Alignment {
+    /// M1 global rigid body motions
+    pub m1: Rbm,
+    /// M2 global rigid body motions
+    pub m2: Rbm,
+}
+impl Alignment {
+    /// Creates a new alignment from the M1 and M2 global rigid body motions
+    pub fn new(m1: Rbm, m2: Rbm) -> Self {
+        Self { m1, m2 }
+    }
+    /// Returns the optical metrics
+    pub fn metrics(&self) -> Metrics {
+        self.metrics_from(&Nominal::new())
+    }
+    /// Returns the optical metrics with respect to the `nominal` telescope
+    fn metrics_from(&self, nominal: &Nominal) -> Metrics {
+        let c = coefficients(&self.m1, &self.m2, nominal.z);
+        let d = |i: usize| c[i] - nominal.c0[i];
+        Metrics {
+            pointing: [d(0), d(1)],
+            focus: d(2),
+            coma: [d(3), d(4)],
+        }
+    }
+    /// Returns the 7 M1 segment rigid body motions
+    pub fn m1_rigidbodymotions(&self) -> Vec<Rbm> {
+        Mirror::<M1>::global_2_rigidbodymotions(&self.m1)
+    }
+    /// Returns the 7 M2 segment rigid body motions
+    pub fn m2_rigidbodymotions(&self) -> Vec<Rbm> {
+        Mirror::<M2>::global_2_rigidbodymotions(&self.m2)
+    }
+    /// Returns the 84 segment rigid body motions of M1 followed by M2
+    pub fn rigidbodymotions(&self) -> Vec<f64> {
+        let mut rbm = Rbm::to_mirror(&self.m1_rigidbodymotions());
+        rbm.extend(Rbm::to_mirror(&self.m2_rigidbodymotions()));
+        rbm
+    }
+    /// Returns the alignment with the M2 global rigid body motions that compensate
+    /// the M1 global misalignment `m1` for the `objective`
+    ///
+    /// The M2 rigid body motions are solved for with Newton iterations on the ray traced metrics,
+    /// an error is returned if the metrics of the `objective` are not compensated within 10 iterations
+    pub fn compensate(m1: Rbm, objective: Objective) -> Result<Self, Error> {
+        let (dofs, metrics) = objective.indices();
+        let n = dofs.len();
+        let m2 = |x: &[f64]| {
+            let mut v = [0f64; 6];
+            dofs.iter().zip(x).for_each(|(&i, x)| v[i] = *x);
+            Rbm::from(v)
+        };
+        let nominal = Nominal::new();
+        let residuals = |x: &[f64]| {
+            let m = Alignment::new(m1, m2(x)).metrics_from(&nominal).to_array();
+            metrics.iter().map(|&i| m[i]).collect::<Vec<f64>>()
+        };
+        let mut x = vec![0f64; n];
+        for _ in 0..10 {
+            let f = residuals(&x);
+            if f.iter().any(|f| !f.is_finite()) {
+                return Err(Error::NonFinite);
+            }
+            // row-major n x n sensitivities
+            let mut jacobian = vec![0f64; n * n];
+            for j in 0..n {
+                let (mut xp, mut xm) = (x.clone(), x.clone());
+                xp[j] += STEP;
+                xm[j] -= STEP;
+                let (fp, fm) = (residuals(&xp), residuals(&xm));
+                for i in 0..n {
+                    jacobian[i * n + j] = (fp[i] - fm[i]) / (2. * STEP);
+                }
+            }
+            let rhs: Vec<f64> = f.iter().map(|f| -f).collect();
+            let dx = linalg::solve(&jacobian, &rhs, n).ok_or(Error::Singular)?;
+            x.iter_mut().zip(&dx).for_each(|(x, dx)| *x += dx);
+            if linalg::dot(&dx, &dx).sqrt() < 1e-13 {
+                break;
+            }
+        }
+        let f = residuals(&x);
+        let residual = linalg::dot(&f, &f).sqrt();
+        if residual < TOLERANCE {
+            Ok(Self::new(m1, m2(&x)))
+        } else {
+            Err(Error::Convergence(residual))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn norm(m: &Metrics, n: usize) -> f64 {
+        m.to_array()[..n].iter().map(|x| x * x).sum::<f64>().sqrt()
+    }
+
+    #[test]
+    fn nominal() {
+        let z = focal_plane();
+        assert!(z < Segment::<M1>::from_id(SegmentId::CENTER).height());
+        assert_eq!(Alignment::default().metrics(), Metrics::default());
+        assert_eq!(Alignment::default().rigidbodymotions().len(), 84);
+        // M1 tilt deviates the beam by twice the tilt angle: the image moves by twice the tilt angle
+        // times the effective focal length
+        let (r, (_, d)) = trace(&Rbm::default(), &Rbm::default())
+            .into_iter()
+            .find_map(|([u, _], ray)| ray.map(|ray| (u * RINGS[RINGS.len() - 1], ray)))
+            .unwrap();
+        let focal_length = r * d.norm() / d[0].abs();
+        let m = Alignment::new(Rbm::new([0.; 3], [1e-6, 0., 0.]), Rbm::default()).metrics();
+        assert!((m.pointing[1].abs() / (2e-6 * focal_length) - 1.).abs() < 5e-3);
+        assert!(m.pointing[0].abs() < 1e-12);
+    }
+
+    #[test]
+    fn pointing_focus() {
+        let m1 = Rbm::new([0., 0., 1e-5], [1e-6, -2e-6, 0.]);
+        assert!(norm(&Alignment::new(m1, Rbm::default()).metrics(), 3) > 1e-6);
+        let alignment = Alignment::compensate(m1, Objective::PointingFocus).unwrap();
+        assert!(norm(&alignment.metrics(), 3) < 1e-12);
+        let rbm = alignment.m2_rigidbodymotions();
+        assert!(rbm[6].rotation.iter().any(|x| x.abs() > 1e-7));
+    }
+
+    #[test]
+    fn large_misalignment() {
+        let m1 = Rbm::new([1e-2, -1e-2, 1e-2], [1e-3, -1e-3, 0.]);
+        let alignment = Alignment::compensate(m1, Objective::ComaFree).unwrap();
+        assert!(norm(&alignment.metrics(), 5) < 1e-12);
+        // the Newton iterations do not converge
+        let m1 = Rbm::new([0.7, -0.7, 0.7], [0.05, -0.05, 0.]);
+        assert!(matches!(
+            Alignment::compensate(m1, Objective::PointingFocus),
+            Err(Error::Convergence(_))
+        ));
+    }
+
+    #[test]
+    fn coma_free() {
+        let m1 = Rbm::new([1e-4, -5e-5, 0.], [0., 0., 0.]);
+        let misaligned = Alignment::new(m1, Rbm::default()).metrics();
+        assert!(misaligned.coma.iter().any(|c| c.abs() > 1e-9));
+        let alignment = Alignment::compensate(m1, Objective::ComaFree).unwrap();
+        assert!(norm(&alignment.metrics(), 5) < 1e-12);
+    }
+}
@@ -0,0 +1,259 @@
+//! Covariance propagation
+//!
+//! A covariance `C` is propagated through a transform with the Jacobian `J` of the transform as `J C Jᵀ`.
+//! The segment transforms are rotations and translations, so the position covariances rotate
+//! with the segment rotation matrix `R` and the rigid body motions covariances with the
+//! block diagonal matrix `[R 0; 0 R]` (small angles approximation for the rotations).
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+use crate::linalg::{matmul, transpose};
+#[cfg(feature = "alloc")]
+use crate::Error;
+use crate::{Gmt, Rbm, Segment, SegmentTrait, Transform, Vector};
+
+/// Returns `J C Jᵀ`
+fn congruence<const N: usize>(j: &[[f64; N]; N], c: &[[f64; N]; N]) -> [[f64; N]; N] {
+    matmul(&matmul(j, c), &transpose(j))
+}
+/// Returns the block diagonal matrix `[R 0; 0 R]`
+fn block_diagonal(r: [[f64; 3]; 3]) -> [[f64; 6]; 6] {
+    let mut b = [[0f64; 6]; 6];
+    for i in 0..3 {
+        for j in 0..3 {
+            b[i][j] = r[i][j];
+            b[i + 3][j + 3] = r[i][j];
+        }
+    }
+    b
+}
+
+/// Vector with a 3x3 covariance
+#[derive(Debug, Clone, PartialEq)]
+pub struct UncertainVector {
+    /// Point or vector coordinates
+    pub vector: Vector,
+    /// Coordinates covariance
+    pub covariance: [[f64; 3]; 3],
+}
+impl UncertainVector {
+    /// Creates a new vector with a covariance
+    pub fn new<V: Into<Vector>>(vector: V, covariance: [[f64; 3]; 3]) -> Self {
+        Self {
+            vector: vector.into(),
+            covariance,
+        }
+    }
+    /// Transforms the point coordinates and the covariance given in the OSS into a segment
+    pub fn fro<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        Self {
+            covariance: congruence(&transpose(&segment.rotation_matrix()), &self.covariance),
+            vector: self.vector.fro(segment),
+        }
+    }
+    /// Transforms the vector and the covariance given in the OSS into a segment
+    pub fn vfrov<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        Self {
+            covariance: congruence(&transpose(&segment.rotation_matrix()), &self.covariance),
+            vector: self.vector.vfrov(segment),
+        }
+    }
+    /// Transforms the point coordinates and the covariance of a segment into the OSS
+    pub fn to<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        Self {
+            covariance: congruence(&segment.rotation_matrix(), &self.covariance),
+            vector: self.vector.to(segment),
+        }
+    }
+    /// Transforms the vector and the covariance of a segment into the OSS
+    pub fn vtov<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        Self {
+            covariance: congruence(&segment.rotation_matrix(), &self.covariance),
+            vector: self.vector.vtov(segment),
+        }
+    }
+}
+
+/// Rigid body motions with a 6x6 covariance
+#[derive(Debug, Clone, PartialEq)]
+pub struct UncertainRbm {
+    /// Rigid body motions
+    pub rbm: Rbm,
+    /// Covariance of `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub covariance: [[f64; 6]; 6],
+}
+impl UncertainRbm {
+    /// Creates new rigid body motions with a covariance
+    pub fn new(rbm: Rbm, covariance: [[f64; 6]; 6]) -> Self {
+        Self { rbm, covariance }
+    }
+    /// Transforms the rigid body motions and the covariance given along the OSS axes into a segment
+    ///
+    /// The rotations are about the segment origin
+    pub fn fro<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let t = Vector::from(self.rbm.translation).vfrov(segment.clone());
+        let q = self.rbm.quaternion();
+        let (r, p, y) = segment
+            .rotation()
+            .map_or(q.clone(), |q_s| q_s.complex_conjugate() * q * q_s)
+            .euler_angles();
+        Self {
+            rbm: Rbm::new(t.into(), [r, p, y]),
+            covariance: congruence(
+                &block_diagonal(transpose(&segment.rotation_matrix())),
+                &self.covariance,
+            ),
+        }
+    }
+    /// Transforms the rigid body motions and the covariance of a segment along the OSS axes
+    ///
+    /// The rotations are about the segment origin
+    pub fn to<M>(self, segment: Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let t = Vector::from(self.rbm.translation).vtov(segment.clone());
+        let q = self.rbm.quaternion();
+        let (r, p, y) = segment
+            .rotation()
+            .map_or(q.clone(), |q_s| &q_s * q * q_s.complex_conjugate())
+            .euler_angles();
+        Self {
+            rbm: Rbm::new(t.into(), [r, p, y]),
+            covariance: congruence(&block_diagonal(segment.rotation_matrix()), &self.covariance),
+        }
+    }
+}
+
+/// Returns the covariance `J C Jᵀ` of the `m`x`n` Jacobian `jacobian` and of the `n`x`n` covariance `covariance`
+///
+/// The matrices are stored row-major
+#[cfg(feature = "alloc")]
+pub fn propagate(jacobian: &[f64], covariance: &[f64], n: usize) -> Result<Vec<f64>, Error> {
+    if covariance.len() != n * n {
+        return Err(Error::MatrixLength(covariance.len(), n * n));
+    }
+    if n == 0 || jacobian.len() % n != 0 {
+        return Err(Error::MatrixLength(jacobian.len(), n));
+    }
+    let m = jacobian.len() / n;
+    let jc: Vec<f64> = jacobian
+        .chunks(n)
+        .flat_map(|row| (0..n).map(move |k| (0..n).map(|l| row[l] * covariance[l * n + k]).sum()))
+        .collect();
+    let mut jcjt = vec![0f64; m * m];
+    for (i, jc) in jc.chunks(n).enumerate() {
+        for (k, j) in jacobian.chunks(n).enumerate() {
+            jcjt[i * m + k] = jc.iter().zip(j).map(|(a, b)| a * b).sum();
+        }
+    }
+    Ok(jcjt)
+}
+
+#[cfg(feature = "alloc")]
+impl<M: Gmt> crate::Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the 42x42 covariance of the segment rigid body motions for the 2x2 covariance of
+    /// the mirror `(tip, tilt)`
+    ///
+    /// The covariance is propagated with [`Mirror::tiptilt_jacobian`](crate::Mirror::tiptilt_jacobian)
+    pub fn tiptilt_covariance(covariance: [[f64; 2]; 2]) -> Vec<f64> {
+        let c: Vec<f64> = covariance.iter().flatten().cloned().collect();
+        // the 42x2 Jacobian and the 2x2 covariance always have compatible lengths
+        propagate(&Self::tiptilt_jacobian(), &c, 2).expect("the tip-tilt Jacobian is 42x2")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mirror, M1, M2};
+
+    #[test]
+    fn vector_tofro() {
+        let c = [[1e-6, 2e-7, 0.], [2e-7, 4e-6, -1e-7], [0., -1e-7, 9e-6]];
+        for segment in Mirror::<M2>::segments() {
+            let u = UncertainVector::new([0.1, -0.2, 0.3], c);
+            let v = u.clone().to(segment.clone());
+            let trace = |c: &[[f64; 3]; 3]| c[0][0] + c[1][1] + c[2][2];
+            assert!((trace(&v.covariance) - trace(&c)).abs() < 1e-18);
+            let w = v.fro(segment);
+            assert!((w.vector - u.vector).norm() < 1e-12);
+            assert!(w
+                .covariance
+                .iter()
+                .flatten()
+                .zip(c.iter().flatten())
+                .all(|(a, b)| (a - b).abs() < 1e-18));
+        }
+    }
+
+    #[test]
+    fn vector_covariance_z() {
+        let segment = Mirror::<M1>::segments().next().unwrap();
+        let mut c = [[0f64; 3]; 3];
+        c[2][2] = 1e-6;
+        let v = UncertainVector::new(Vector::k(), c).vtov(segment.clone());
+        // the covariance is along the segment z-axis in the OSS
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((v.covariance[i][j] - 1e-6 * v.vector[i] * v.vector[j]).abs() < 1e-18);
+            }
+        }
+    }
+
+    #[test]
+    fn rbm_tofro() {
+        let mut c = [[0f64; 6]; 6];
+        (0..6).for_each(|i| c[i][i] = (i + 1) as f64 * 1e-12);
+        c[0][3] = 5e-13;
+        c[3][0] = 5e-13;
+        for segment in Mirror::<M1>::segments() {
+            let u = UncertainRbm::new(Rbm::new([1e-6, 0., 2e-6], [1e-6, -1e-6, 0.]), c);
+            let w = u.clone().to(segment.clone()).fro(segment);
+            assert!(w
+                .covariance
+                .iter()
+                .flatten()
+                .zip(c.iter().flatten())
+                .all(|(a, b)| (a - b).abs() < 1e-24));
+            let (a, b): ([f64; 6], [f64; 6]) = (w.rbm.into(), u.rbm.into());
+            assert!(a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-15));
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn tiptilt() {
+        let c = Mirror::<M1>::tiptilt_covariance([[1e-12, 0.], [0., 4e-12]]);
+        assert_eq!(c.len(), 42 * 42);
+        // center segment Rx and Ry variances
+        assert!((c[39 * 42 + 39] - 1e-12).abs() < 1e-24);
+        assert!((c[40 * 42 + 40] - 4e-12).abs() < 1e-24);
+        assert!(propagate(&[1., 2., 3.], &[1., 0., 0., 1.], 2).is_err());
+    }
+}
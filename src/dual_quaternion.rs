@@ -0,0 +1,313 @@
+//! Dual quaternions
+//!
+//! A unit [`DualQuaternion`] `r + εd` encodes the rotation [`Quaternion`] `r` followed by
+//! the translation `t` with the dual part `d = t r / 2`.
+//! The product of 2 dual quaternions `a * b` is the transform `b` followed by the transform `a`,
+//! so the pose of a segment displaced by its rigid body motions and by the mirror global motion
+//! is `global * segment.pose() * rbm`.
+
+use core::ops::Mul;
+
+use crate::{Error, Gmt, Quaternion, Rbm, Real, Segment, SegmentTrait, Vector};
+
+/// Returns the coefficients `(a,b,c)` of `V = I + a[w]x + b[w]x²` and of `V⁻¹ = I - [w]x/2 + c[w]x²`
+/// for the rotation angle `theta`
+fn coefficients<T: Real>(theta: T) -> (T, T, T) {
+    let t2 = theta * theta;
+    let n = T::from_f64;
+    if theta < T::epsilon().sqrt().sqrt() {
+        (
+            n(0.5) - t2 / n(24.),
+            n(1. / 6.) - t2 / n(120.),
+            n(1. / 12.) + t2 / n(720.),
+        )
+    } else {
+        let h = n(0.5) * theta;
+        let sinc = h.sin() / theta;
+        (
+            n(2.) * sinc * sinc,
+            (theta - theta.sin()) / (t2 * theta),
+            (T::one() - h / h.tan()) / t2,
+        )
+    }
+}
+
+/// Dual quaternion
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DualQuaternion<T = f64> {
+    real: Quaternion<T>,
+    dual: Quaternion<T>,
+}
+impl<T: Real> DualQuaternion<T> {
+    /// Creates a dual quaternion from the rotation `rotation` followed by the translation `translation`
+    ///
+    /// An error is returned if `rotation` is not a unit quaternion
+    pub fn new<V: Into<Vector<T>>>(rotation: Quaternion<T>, translation: V) -> Result<Self, Error> {
+        rotation.check_unit(T::from_f64(1e-9))?;
+        Ok(Self::compose(rotation, translation))
+    }
+    /// Creates a dual quaternion from the unit quaternion `rotation` followed by the translation `translation`
+    pub(crate) fn compose<V: Into<Vector<T>>>(rotation: Quaternion<T>, translation: V) -> Self {
+        let dual = Quaternion::from(translation.into()) * &rotation / (T::one() + T::one());
+        Self {
+            real: rotation,
+            dual,
+        }
+    }
+    /// Creates a dual quaternion from the pure rotation `rotation`
+    pub fn rotation(rotation: Quaternion<T>) -> Self {
+        Self {
+            real: rotation,
+            dual: Quaternion::from(Vector::null()),
+        }
+    }
+    /// Creates a dual quaternion from the pure translation `translation`
+    pub fn translation<V: Into<Vector<T>>>(translation: V) -> Self {
+        Self::compose(Quaternion::identity(), translation)
+    }
+    /// Returns the identity
+    pub fn identity() -> Self {
+        Self::rotation(Quaternion::identity())
+    }
+    /// Returns the real part, i.e. the rotation quaternion
+    pub fn real(&self) -> &Quaternion<T> {
+        &self.real
+    }
+    /// Returns the dual part
+    pub fn dual(&self) -> &Quaternion<T> {
+        &self.dual
+    }
+    /// Returns the translation vector
+    pub fn translation_vector(&self) -> Vector<T> {
+        let t = (&self.dual * self.real.complex_conjugate()).vector();
+        &t * (T::one() + T::one())
+    }
+    /// Returns the quaternion conjugate of both the real and the dual parts
+    ///
+    /// This is the inverse of a unit dual quaternion
+    pub fn conjugate(&self) -> Self {
+        Self {
+            real: self.real.complex_conjugate(),
+            dual: self.dual.complex_conjugate(),
+        }
+    }
+    /// Returns the inverse transform
+    pub fn inverse(&self) -> Self {
+        self.conjugate()
+    }
+    /// Applies the rotation to the vector `v`
+    pub fn rotate(&self, v: Vector<T>) -> Vector<T> {
+        (&self.real * v * self.real.complex_conjugate()).vector()
+    }
+    /// Applies the rotation and the translation to the point `v`
+    pub fn transform(&self, v: Vector<T>) -> Vector<T> {
+        self.rotate(v) + self.translation_vector()
+    }
+    /// Returns the twist `[vx,vy,vz,wx,wy,wz]` of the transform
+    ///
+    /// The twist coordinates are ordered as in [`Se3::log`](crate::Se3::log), the rotation angle is in the range \[0,π\]
+    pub fn log(&self) -> [T; 6] {
+        let r = if self.real.scalar() < T::zero() {
+            Quaternion::from_parts(-self.real.scalar(), -self.real.vector())
+        } else {
+            self.real.clone()
+        };
+        let u = r.vector();
+        let s = u.norm();
+        let theta = (T::one() + T::one()) * s.atan2(r.scalar());
+        let w = if s > T::zero() { &u * (theta / s) } else { u };
+        let (_, _, c) = coefficients(theta);
+        let t = self.translation_vector();
+        let wt = w.cross(&t);
+        let v = t - &wt / (T::one() + T::one()) + &w.cross(&wt) * c;
+        [v[0], v[1], v[2], w[0], w[1], w[2]]
+    }
+    /// Returns the transform of the twist `[vx,vy,vz,wx,wy,wz]`
+    ///
+    /// The twist coordinates are ordered as in [`Se3::exp`](crate::Se3::exp)
+    pub fn exp(twist: [T; 6]) -> Self {
+        let [vx, vy, vz, wx, wy, wz] = twist;
+        let (v, w) = (Vector::from([vx, vy, vz]), Vector::from([wx, wy, wz]));
+        let theta = w.norm();
+        let (a, b, _) = coefficients(theta);
+        let wv = w.cross(&v);
+        let t = v + &wv * a + &w.cross(&wv) * b;
+        if theta > T::zero() {
+            Self::compose(Quaternion::rotation(theta, &w), t)
+        } else {
+            Self::translation(t)
+        }
+    }
+    /// Returns the power `s` of the transform, i.e. the fraction `s` of the screw motion
+    pub fn powf(&self, s: T) -> Self {
+        let mut twist = self.log();
+        twist.iter_mut().for_each(|x| *x = *x * s);
+        Self::exp(twist)
+    }
+    /// Screw linear interpolation between `self` (`s=0`) and `other` (`s=1`)
+    pub fn sclerp(&self, other: &Self, s: T) -> Self {
+        self * &(&self.inverse() * other).powf(s)
+    }
+    /// Converts the dual quaternion to another floating point type
+    pub fn cast<U: Real>(&self) -> DualQuaternion<U> {
+        DualQuaternion {
+            real: self.real.cast(),
+            dual: self.dual.cast(),
+        }
+    }
+}
+impl<T: Real> Mul for &DualQuaternion<T> {
+    type Output = DualQuaternion<T>;
+    /// Returns the transform `rhs` followed by the transform `self`
+    fn mul(self, rhs: &DualQuaternion<T>) -> DualQuaternion<T> {
+        DualQuaternion {
+            real: &self.real * &rhs.real,
+            dual: &self.real * &rhs.dual + &self.dual * &rhs.real,
+        }
+    }
+}
+impl<T: Real> Mul for DualQuaternion<T> {
+    type Output = DualQuaternion<T>;
+    /// Returns the transform `rhs` followed by the transform `self`
+    fn mul(self, rhs: DualQuaternion<T>) -> DualQuaternion<T> {
+        &self * &rhs
+    }
+}
+impl<T: Real> Mul<&DualQuaternion<T>> for DualQuaternion<T> {
+    type Output = DualQuaternion<T>;
+    /// Returns the transform `rhs` followed by the transform `self`
+    fn mul(self, rhs: &DualQuaternion<T>) -> DualQuaternion<T> {
+        &self * rhs
+    }
+}
+impl<T: Real> PartialEq for DualQuaternion<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.real == other.real && self.dual == other.dual
+    }
+}
+impl From<&Rbm> for DualQuaternion {
+    fn from(rbm: &Rbm) -> Self {
+        Self::compose(rbm.quaternion(), rbm.translation)
+    }
+}
+impl From<Rbm> for DualQuaternion {
+    fn from(rbm: Rbm) -> Self {
+        Self::from(&rbm)
+    }
+}
+impl From<&DualQuaternion> for Rbm {
+    fn from(dq: &DualQuaternion) -> Self {
+        let (r, p, y) = dq.real.euler_angles();
+        Rbm::new(dq.translation_vector().into(), [r, p, y])
+    }
+}
+
+impl<M: Gmt> Segment<M>
+where
+    Segment<M>: SegmentTrait,
+{
+    /// Returns the pose of the segment, the transform from the segment coordinates to the OSS
+    pub fn pose(&self) -> DualQuaternion {
+        DualQuaternion::compose(
+            self.rotation().unwrap_or_else(Quaternion::identity),
+            self.translation(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "alloc")]
+    use crate::Transform;
+    use crate::{Mirror, Se3, M1, M2};
+
+    /// Returns the segment pose `q v q* + t` and its inverse `q* (v - t) q` of `v`
+    fn explicit<M: Gmt>(segment: &Segment<M>, v: &Vector) -> (Vector, Vector)
+    where
+        Segment<M>: SegmentTrait,
+    {
+        let q = segment.rotation().unwrap_or_else(Quaternion::identity);
+        let t = segment.translation();
+        (
+            (&q * v.clone() * q.complex_conjugate()).vector() + &t,
+            (q.complex_conjugate() * (v.clone() - t) * &q).vector(),
+        )
+    }
+
+    #[test]
+    fn pose() {
+        let v = Vector::from([0.1, -0.2, 0.05]);
+        for segment in Mirror::<M2>::segments() {
+            let pose = segment.pose();
+            let (to, fro) = explicit(&segment, &v);
+            assert!((pose.transform(v.clone()) - to).norm() < 1e-14);
+            assert!((pose.inverse().transform(v.clone()) - fro).norm() < 1e-14);
+            assert!((pose.translation_vector() - segment.translation()).norm() < 1e-14);
+        }
+        // M1 center segment: the translation is the segment height only
+        let segment = Mirror::<M1>::center_segment();
+        let u = segment.pose().transform(v.clone());
+        assert!((u - Vector::from([0.1, -0.2, 0.05 + segment.height()])).norm() < 1e-14);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn composition() {
+        let v = Vector::from([0.5, -1., 0.02]);
+        let global = Rbm::new([1e-4, -2e-4, 5e-5], [2e-5, -1e-5, 3e-5]);
+        // global motion about the center segment origin
+        let v7 = Mirror::<M1>::center_segment().translation();
+        let g = DualQuaternion::translation(v7.clone())
+            * DualQuaternion::from(global)
+            * DualQuaternion::translation(-v7);
+        for (segment, rbm) in
+            Mirror::<M1>::segments().zip(Mirror::<M1>::global_2_rigidbodymotions(&global))
+        {
+            let u = (&g * &segment.pose()).transform(v.clone());
+            let w = (segment.pose() * DualQuaternion::from(rbm)).transform(v.clone());
+            assert!((u.clone() - w).norm() < 1e-13);
+            assert!((u - rbm.apply(v.clone()).to(segment)).norm() < 1e-13);
+        }
+    }
+
+    #[test]
+    fn sclerp() {
+        let a = DualQuaternion::from(Rbm::new([0.1, -0.2, 0.3], [0.4, -0.5, 0.6]));
+        let b = DualQuaternion::from(Rbm::new([-1., 0.5, 0.], [0., 0.2, -0.1]));
+        let v = Vector::from([0.5, -1., 0.2]);
+        assert!((a.sclerp(&b, 0.).transform(v.clone()) - a.transform(v.clone())).norm() < 1e-14);
+        assert!((a.sclerp(&b, 1.).transform(v.clone()) - b.transform(v.clone())).norm() < 1e-12);
+        let m = Se3::from(&a.sclerp(&b, 0.3));
+        let n = Se3::from(&a).interpolate(&Se3::from(&b), 0.3);
+        assert!((m.apply(v.clone()) - n.apply(v)).norm() < 1e-12);
+        // pure translation
+        let t = DualQuaternion::translation([1., 2., 3.]).powf(0.5);
+        assert!((t.translation_vector() - Vector::from([0.5, 1., 1.5])).norm() < 1e-15);
+    }
+
+    #[test]
+    fn non_unit_rotation() {
+        let q = Rbm::new([0.; 3], [0.1, -0.2, 0.3]).quaternion();
+        let dq = DualQuaternion::new(q.clone(), [1., 2., 3.]).unwrap();
+        assert!((dq.translation_vector() - Vector::from([1., 2., 3.])).norm() < 1e-15);
+        assert!(matches!(
+            DualQuaternion::new(2. * &q, [1., 2., 3.]),
+            Err(Error::NonUnitRotation(_))
+        ));
+    }
+
+    #[test]
+    fn f32_pose() {
+        let v = Vector::from([0.1, -0.2, 0.05]);
+        for segment in Mirror::<M1>::segments() {
+            let pose = segment.pose().cast::<f32>();
+            let (to, fro) = explicit(&segment, &v);
+            let u = pose.transform(v.cast::<f32>()).cast::<f64>();
+            assert!((u - to).norm() < 1e-5);
+            let u = pose.inverse().transform(v.cast::<f32>()).cast::<f64>();
+            assert!((u - fro).norm() < 1e-5);
+        }
+    }
+}
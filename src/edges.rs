@@ -0,0 +1,255 @@
+//! Gaps and dihedral angles between neighbouring segments
+//!
+//! Each outer segment shares an edge with its 2 neighbouring outer segments and with the center segment,
+//! leading to 12 edges per mirror.
+//! The edge metrics are computed from the segment outer rims and surfaces after applying
+//! the segment rigid body motions.
+//! The gap is the signed distance between the closest points of the rims:
+//! it is negative if the rim of a segment is inside the aperture of its neighbour.
+
+use std::f64::consts::PI;
+
+use crate::{
+    Error, Gmt, Mirror, Quaternion, Rbm, Segment, SegmentId, SegmentTrait, Transform, Vector,
+};
+
+/// Metrics of the edge shared by 2 neighbouring segments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    /// Segment ids `(a,b)`
+    pub segments: (SegmentId, SegmentId),
+    /// Signed distance between the segment rims, negative if the segments overlap \[m\]
+    pub gap: f64,
+    /// Height of segment `b` surface minus the height of segment `a` surface at the edge middle \[m\]
+    pub step: f64,
+    /// Angle between the segment surface normals at the edge middle, positive if segment `b`
+    /// is rotated toward the OSS z-axis with respect to segment `a`, around the edge direction \[rd\]
+    pub dihedral: f64,
+}
+impl Edge {
+    /// Checks if the gap is less than the `clearance`
+    pub fn collides(&self, clearance: f64) -> bool {
+        self.gap < clearance
+    }
+}
+
+/// Segment with rigid body motions
+struct Pose<M: Gmt> {
+    segment: Segment<M>,
+    rbm: Rbm,
+    q: Quaternion,
+}
+impl<M: Gmt> Pose<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    fn new(segment: Segment<M>, rbm: Rbm) -> Self {
+        Self {
+            segment,
+            q: rbm.quaternion(),
+            rbm,
+        }
+    }
+    /// Transforms the point `v` of the segment coordinates into the OSS
+    fn to_oss(&self, v: Vector) -> Vector {
+        self.rbm.apply(v).to(self.segment.clone())
+    }
+    /// Transforms the point `p` of the OSS into the segment coordinates
+    fn fro_oss(&self, p: Vector) -> Vector {
+        let v = p.fro(self.segment.clone()) - Vector::from(self.rbm.translation);
+        (self.q.complex_conjugate() * v * &self.q).vector()
+    }
+    /// Returns the rim point at the azimuth `o` in the OSS
+    fn rim(&self, o: f64) -> Vector {
+        let r = self.segment.aperture().outer_radius();
+        let (s, c) = o.sin_cos();
+        let (x, y) = (r * c, r * s);
+        self.to_oss(Vector::from([x, y, self.segment.surface(x, y)]))
+    }
+    /// Returns the point and the unit normal of the segment surface above the OSS coordinates `(x,y)`
+    fn surface(&self, x: f64, y: f64) -> (Vector, Vector) {
+        let v = Vector::from([x, y, self.segment.parent_surface(x, y)]).fro(self.segment.clone());
+        let (mut u, mut w) = (v[0], v[1]);
+        let mut p = self.to_oss(Vector::from([u, w, self.segment.surface(u, w)]));
+        for _ in 0..50 {
+            let d = Vector::from([x - p[0], y - p[1], 0.]);
+            if d.norm() < 1e-14 {
+                break;
+            }
+            let d = d.vfrov(self.segment.clone());
+            let d = (self.q.complex_conjugate() * d * &self.q).vector();
+            u += d[0];
+            w += d[1];
+            p = self.to_oss(Vector::from([u, w, self.segment.surface(u, w)]));
+        }
+        // nominal normal in the OSS
+        let p0 = Vector::from([u, w, self.segment.surface(u, w)]).to(self.segment.clone());
+        let rho = p0[0].hypot(p0[1]);
+        let s = self.segment.conic().slope(rho);
+        let n0 = if rho > 0f64 {
+            Vector::from([-s * p0[0] / rho, -s * p0[1] / rho, 1.])
+        } else {
+            Vector::k()
+        };
+        let n = self
+            .rbm
+            .rotate((&n0 / n0.norm()).vfrov(self.segment.clone()))
+            .vtov(self.segment.clone());
+        (p, n)
+    }
+}
+
+/// Returns the signed distance from the rim point of `a` at the azimuth `o` to the rim of `b`
+/// and the 2 rim points
+///
+/// The closest rim point of `b` is at the azimuth of the rim point of `a` in the coordinates of `b`,
+/// the distance is negative if the rim point of `a` is inside the aperture of `b`
+fn rim_distance<M: Gmt>(a: &Pose<M>, b: &Pose<M>, o: f64) -> (f64, Vector, Vector)
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let pa = a.rim(o);
+    let v = b.fro_oss(pa.clone());
+    let pb = b.rim(v[1].atan2(v[0]));
+    let d = (pb.clone() - &pa).norm();
+    if v[0].hypot(v[1]) < b.segment.aperture().outer_radius() {
+        (-d, pa, pb)
+    } else {
+        (d, pa, pb)
+    }
+}
+
+/// Returns the signed distance between the rims of `a` and `b` and the closest rim points
+///
+/// The rim point of `a` is searched within 45 degrees of the azimuth facing the origin of `b`
+fn closest<M: Gmt>(a: &Pose<M>, b: &Pose<M>) -> (f64, Vector, Vector)
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let v = a.fro_oss(b.to_oss(Vector::null()));
+    let o = v[1].atan2(v[0]);
+    let (mut lo, mut hi) = (o - 0.25 * PI, o + 0.25 * PI);
+    for _ in 0..40 {
+        let m1 = lo + (hi - lo) / 3.;
+        let m2 = hi - (hi - lo) / 3.;
+        if rim_distance(a, b, m1).0 < rim_distance(a, b, m2).0 {
+            hi = m2;
+        } else {
+            lo = m1;
+        }
+    }
+    rim_distance(a, b, 0.5 * (lo + hi))
+}
+
+/// Returns the metrics of the edge between the segments `a` and `b`
+fn edge<M: Gmt>(a: &Pose<M>, b: &Pose<M>) -> Edge
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let (gap, pa, pb) = closest(a, b);
+    // edge direction, perpendicular to the direction between the segment origins in the OSS x-y plane
+    let (ca, cb) = (a.to_oss(Vector::null()), b.to_oss(Vector::null()));
+    let t = Vector::k().cross(&(cb - ca));
+    let t = &t / t.norm();
+    let (x, y) = (0.5 * (pa[0] + pb[0]), 0.5 * (pa[1] + pb[1]));
+    let (sa, na) = a.surface(x, y);
+    let (sb, nb) = b.surface(x, y);
+    Edge {
+        segments: (a.segment.segment_id(), b.segment.segment_id()),
+        gap,
+        step: sb[2] - sa[2],
+        dihedral: na.cross(&nb).dot(&t).asin(),
+    }
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the ids of the 12 pairs of neighbouring segments
+    pub fn neighbours() -> Vec<(SegmentId, SegmentId)> {
+        SegmentId::outer()
+            .zip(SegmentId::outer().cycle().skip(1))
+            .chain(SegmentId::outer().map(|a| (a, SegmentId::CENTER)))
+            .collect()
+    }
+    /// Returns the metrics of the 12 edges for the mirror rigid body motions `rbm`
+    ///
+    /// `rbm` is the 42 elements vector of the 7 segment rigid body motions in the segment coordinates
+    pub fn edges(rbm: &[f64]) -> Result<Vec<Edge>, Error> {
+        let poses: Vec<_> = Self::segments()
+            .zip(Rbm::from_mirror(rbm)?)
+            .map(|(segment, rbm)| Pose::new(segment, rbm))
+            .collect();
+        Ok(Self::neighbours()
+            .into_iter()
+            .map(|(a, b)| edge(&poses[a.index()], &poses[b.index()]))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[test]
+    fn m1_nominal() {
+        let edges = Mirror::<M1>::edges(&[0f64; 42]).unwrap();
+        assert_eq!(edges.len(), 12);
+        for e in &edges {
+            assert!(e.gap > 0.1 && e.gap < 1.);
+            assert!(e.step.abs() < 1e-9, "{:?}", e);
+            assert!(e.dihedral.abs() < 1e-9, "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn m1_piston() {
+        let mut rbm = [0f64; 42];
+        rbm[2] = 1e-6;
+        let edges = Mirror::<M1>::edges(&rbm).unwrap();
+        // segment #1 edges with segments #2 and #7
+        assert!((edges[0].step + 1e-6).abs() < 1e-7);
+        assert!((edges[6].step + 1e-6).abs() < 1e-7);
+        assert!(edges[1].step.abs() < 1e-12);
+    }
+
+    #[test]
+    fn m2_tilt() {
+        let nominal = Mirror::<M2>::edges(&[0f64; 42]).unwrap();
+        let mut rbm = [0f64; 42];
+        rbm[6 * 6 + 3] = 1e-4;
+        let edges = Mirror::<M2>::edges(&rbm).unwrap();
+        let tilted: Vec<_> = edges.iter().filter(|e| e.dihedral.abs() > 1e-5).collect();
+        assert!(tilted.iter().all(|e| e.segments.1 == SegmentId::CENTER) && tilted.len() >= 4);
+        assert!(edges
+            .iter()
+            .zip(&nominal)
+            .all(|(e, n)| (e.gap - n.gap).abs() < 1e-3 && !e.collides(0.)));
+    }
+
+    #[test]
+    fn m1_overlap() {
+        let (segment, center) = (
+            Segment::<M1>::new(1).unwrap(),
+            Mirror::<M1>::center_segment(),
+        );
+        let (gap, pa, pb) = closest(
+            &Pose::new(segment.clone(), Rbm::default()),
+            &Pose::new(center, Rbm::default()),
+        );
+        let u = pb - pa;
+        let u = &u / u.norm();
+        // segment #1 moved toward the center segment until the rims touch, then overlap
+        for (shift, expected) in [(1., 0.), (1.5, -0.5)] {
+            let mut rbm = [0f64; 42];
+            let t = (&u * (shift * gap)).vfrov(segment.clone());
+            rbm[..3].copy_from_slice(&<[f64; 3]>::from(t));
+            let e = &Mirror::<M1>::edges(&rbm).unwrap()[6];
+            assert_eq!(e.segments, (segment.segment_id(), SegmentId::CENTER));
+            assert!((e.gap - expected * gap).abs() < 1e-9, "{:?}", e);
+            assert!(e.dihedral.is_finite() && e.collides(1e-3), "{:?}", e);
+        }
+    }
+}
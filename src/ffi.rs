@@ -0,0 +1,349 @@
+//! C ABI
+//!
+//! The C declarations are in `include/geotrans.h`.
+//! All the functions return a [`Status`] code and never unwind across the FFI boundary.
+//! The point and vector functions return [`Status::InvalidLength`] if `3*n_point` doubles
+//! overflow the address space.
+
+use std::{
+    ffi::{c_char, c_int},
+    mem,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{Gmt, Mirror, Segment, SegmentTrait, Transform, Vector, M1, M2};
+
+/// Status codes
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidMirror = 2,
+    InvalidSegment = 3,
+    InvalidLength = 4,
+    Panic = 5,
+}
+
+/// Mirror selection: 1 for M1, 2 for M2
+const MIRROR_M1: c_int = 1;
+const MIRROR_M2: c_int = 2;
+
+/// Opaque segment handle
+pub enum GeotransSegment {
+    M1(Segment<M1>),
+    M2(Segment<M2>),
+}
+
+/// Runs `f`, turning panics into [`Status::Panic`]
+fn guard<F: FnOnce() -> Status>(f: F) -> Status {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(Status::Panic)
+}
+
+/// Creates a new segment of the `mirror` (1 for M1, 2 for M2) with the given `id` in the range \[1,7\]
+///
+/// The segment must be released with [`geotrans_segment_free`].
+/// On error, `*segment` is set to null
+///
+/// # Safety
+/// `segment` must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_segment_new(
+    mirror: c_int,
+    id: c_int,
+    segment: *mut *mut GeotransSegment,
+) -> Status {
+    guard(|| {
+        if segment.is_null() {
+            return Status::NullPointer;
+        }
+        *segment = ptr::null_mut();
+        let handle = match mirror {
+            MIRROR_M1 => Segment::<M1>::new(id).map(GeotransSegment::M1),
+            MIRROR_M2 => Segment::<M2>::new(id).map(GeotransSegment::M2),
+            _ => return Status::InvalidMirror,
+        };
+        match handle {
+            Ok(handle) => {
+                *segment = Box::into_raw(Box::new(handle));
+                Status::Ok
+            }
+            Err(_) => Status::InvalidSegment,
+        }
+    })
+}
+
+/// Releases a segment created with [`geotrans_segment_new`]
+///
+/// # Safety
+/// `segment` must be null or a pointer returned by [`geotrans_segment_new`] and not already released
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_segment_free(segment: *mut GeotransSegment) {
+    if !segment.is_null() {
+        drop(Box::from_raw(segment));
+    }
+}
+
+/// Writes the segment origin coordinates in the OSS into `xyz`
+///
+/// # Safety
+/// `segment` must be a valid segment and `xyz` must point to 3 doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_segment_translation(
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+) -> Status {
+    guard(|| {
+        if segment.is_null() || xyz.is_null() {
+            return Status::NullPointer;
+        }
+        let t = match &*segment {
+            GeotransSegment::M1(s) => s.translation(),
+            GeotransSegment::M2(s) => s.translation(),
+        };
+        slice::from_raw_parts_mut(xyz, 3).copy_from_slice(&t);
+        Status::Ok
+    })
+}
+
+/// Writes the segment rotation quaternion `[w,x,y,z]` into `q`
+///
+/// The identity quaternion is returned for a segment without rotation
+///
+/// # Safety
+/// `segment` must be a valid segment and `q` must point to 4 doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_segment_rotation(
+    segment: *const GeotransSegment,
+    q: *mut f64,
+) -> Status {
+    guard(|| {
+        if segment.is_null() || q.is_null() {
+            return Status::NullPointer;
+        }
+        let rotation = match &*segment {
+            GeotransSegment::M1(s) => s.rotation(),
+            GeotransSegment::M2(s) => s.rotation(),
+        };
+        let q = slice::from_raw_parts_mut(q, 4);
+        match rotation {
+            Some(r) => {
+                q[0] = r.scalar();
+                q[1..].copy_from_slice(r.vector_as_slice());
+            }
+            None => q.copy_from_slice(&[1., 0., 0., 0.]),
+        }
+        Status::Ok
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    To,
+    Fro,
+    Vtov,
+    Vfrov,
+}
+fn apply<M: Gmt>(operation: Operation, segment: &Segment<M>, xyz: &mut [f64])
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    for p in xyz.chunks_exact_mut(3) {
+        let v = Vector::from([p[0], p[1], p[2]]);
+        let v = match operation {
+            Operation::To => v.to(segment.clone()),
+            Operation::Fro => v.fro(segment.clone()),
+            Operation::Vtov => v.vtov(segment.clone()),
+            Operation::Vfrov => v.vfrov(segment.clone()),
+        };
+        p.copy_from_slice(&v);
+    }
+}
+unsafe fn transform(
+    operation: Operation,
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+    n_point: usize,
+) -> Status {
+    guard(|| {
+        if segment.is_null() || (xyz.is_null() && n_point > 0) {
+            return Status::NullPointer;
+        }
+        if n_point == 0 {
+            return Status::Ok;
+        }
+        // a slice cannot span more than `isize::MAX` bytes
+        let len = match n_point
+            .checked_mul(3)
+            .filter(|&len| len <= isize::MAX as usize / mem::size_of::<f64>())
+        {
+            Some(len) => len,
+            None => return Status::InvalidLength,
+        };
+        let xyz = slice::from_raw_parts_mut(xyz, len);
+        match &*segment {
+            GeotransSegment::M1(s) => apply(operation, s, xyz),
+            GeotransSegment::M2(s) => apply(operation, s, xyz),
+        }
+        Status::Ok
+    })
+}
+
+/// Transforms in place the coordinates of `n_point` segment points `[x0,y0,z0,x1,...]` into the OSS
+///
+/// # Safety
+/// `segment` must be a valid segment and `xyz` must point to `3*n_point` doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_to(
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+    n_point: usize,
+) -> Status {
+    transform(Operation::To, segment, xyz, n_point)
+}
+/// Transforms in place the coordinates of `n_point` OSS points `[x0,y0,z0,x1,...]` into the segment
+///
+/// # Safety
+/// `segment` must be a valid segment and `xyz` must point to `3*n_point` doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_fro(
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+    n_point: usize,
+) -> Status {
+    transform(Operation::Fro, segment, xyz, n_point)
+}
+/// Transforms in place `n_point` segment vectors `[x0,y0,z0,x1,...]` into the OSS
+///
+/// # Safety
+/// `segment` must be a valid segment and `xyz` must point to `3*n_point` doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_vtov(
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+    n_point: usize,
+) -> Status {
+    transform(Operation::Vtov, segment, xyz, n_point)
+}
+/// Transforms in place `n_point` OSS vectors `[x0,y0,z0,x1,...]` into the segment
+///
+/// # Safety
+/// `segment` must be a valid segment and `xyz` must point to `3*n_point` doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_vfrov(
+    segment: *const GeotransSegment,
+    xyz: *mut f64,
+    n_point: usize,
+) -> Status {
+    transform(Operation::Vfrov, segment, xyz, n_point)
+}
+
+/// Writes the 42 segment rigid body motions of the `mirror` tip-tilt into `rbm`
+///
+/// `len` is the length of `rbm` and must be 42
+///
+/// # Safety
+/// `rbm` must point to `len` doubles
+#[no_mangle]
+pub unsafe extern "C" fn geotrans_tiptilt_to_rbm(
+    mirror: c_int,
+    tip: f64,
+    tilt: f64,
+    rbm: *mut f64,
+    len: usize,
+) -> Status {
+    guard(|| {
+        if rbm.is_null() {
+            return Status::NullPointer;
+        }
+        if len != 42 {
+            return Status::InvalidLength;
+        }
+        let values = match mirror {
+            MIRROR_M1 => Mirror::<M1>::tiptilt_2_rigidbodymotions((tip, tilt)),
+            MIRROR_M2 => Mirror::<M2>::tiptilt_2_rigidbodymotions((tip, tilt)),
+            _ => return Status::InvalidMirror,
+        };
+        slice::from_raw_parts_mut(rbm, len).copy_from_slice(&values);
+        Status::Ok
+    })
+}
+
+/// Returns a static null terminated description of the `status` code
+#[no_mangle]
+pub extern "C" fn geotrans_status_message(status: c_int) -> *const c_char {
+    let message: &'static [u8] = match status {
+        0 => b"ok\0",
+        1 => b"null pointer\0",
+        2 => b"invalid mirror, expected 1 (M1) or 2 (M2)\0",
+        3 => b"invalid segment id, expected a value in the range [1,7]\0",
+        4 => b"invalid buffer length\0",
+        5 => b"internal error\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_tofro() {
+        unsafe {
+            let mut segment = ptr::null_mut();
+            assert_eq!(geotrans_segment_new(1, 3, &mut segment), Status::Ok);
+            let mut xyz = [0.1, 0.2, 0., -0.3, 0.1, 0.];
+            assert_eq!(geotrans_to(segment, xyz.as_mut_ptr(), 2), Status::Ok);
+            let v = Vector::from([0.1, 0.2, 0.]).to(Segment::<M1>::new(3).unwrap());
+            assert_eq!(&xyz[..3], &*v);
+            assert_eq!(geotrans_fro(segment, xyz.as_mut_ptr(), 2), Status::Ok);
+            assert!((xyz[3] + 0.3).abs() < 1e-12);
+            let mut q = [0f64; 4];
+            assert_eq!(
+                geotrans_segment_rotation(segment, q.as_mut_ptr()),
+                Status::Ok
+            );
+            assert!((q.iter().map(|x| x * x).sum::<f64>() - 1.).abs() < 1e-12);
+            geotrans_segment_free(segment);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        unsafe {
+            let mut segment = ptr::null_mut();
+            assert_eq!(
+                geotrans_segment_new(1, 8, &mut segment),
+                Status::InvalidSegment
+            );
+            assert!(segment.is_null());
+            segment = ptr::NonNull::dangling().as_ptr();
+            assert_eq!(
+                geotrans_segment_new(3, 1, &mut segment),
+                Status::InvalidMirror
+            );
+            assert!(segment.is_null());
+            assert_eq!(
+                geotrans_to(segment, ptr::null_mut(), 1),
+                Status::NullPointer
+            );
+            assert_eq!(geotrans_segment_new(1, 1, &mut segment), Status::Ok);
+            let xyz = ptr::NonNull::dangling().as_ptr();
+            for n_point in [usize::MAX, usize::MAX / 3 + 1, isize::MAX as usize / 8] {
+                assert_eq!(geotrans_vfrov(segment, xyz, n_point), Status::InvalidLength);
+            }
+            geotrans_segment_free(segment);
+            let mut rbm = [0f64; 42];
+            assert_eq!(
+                geotrans_tiptilt_to_rbm(2, 1e-6, 0., rbm.as_mut_ptr(), 41),
+                Status::InvalidLength
+            );
+            assert_eq!(
+                geotrans_tiptilt_to_rbm(2, 1e-6, 0., rbm.as_mut_ptr(), 42),
+                Status::Ok
+            );
+        }
+    }
+}
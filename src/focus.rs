@@ -0,0 +1,215 @@
+//! Focus mode
+//!
+//! The focus mode moves M2 along the OSS z-axis by `Δz`: the M2 parent conic vertex is shifted
+//! by `Δz` and the outer M2 segments slide on the shifted parent conic to stay in the beam
+//! converging from the M1 focus, they translate radially and along the OSS z-axis and tilt radially.
+//! The paraxial focus of the telescope moves along the OSS z-axis by [`Focus::shift`].
+//!
+//! The same focus shift is obtained with M1 by scaling the M1 parent conic about its vertex by
+//! the strain [`Focus::m1_strain`]: the outer segments move radially and along the OSS z-axis
+//! on the scaled parent conic, the conic slope and the segment orientations are unchanged.
+//! To first order, the M2 rigid body motions together with the opposite M1 rigid body motions
+//! keep the focal plane fixed.
+//!
+//! [`Mirror::focus_rigidbodymotions`] gives the segment motions of the focus mode.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::{Gmt, Mirror, Rbm};
+use crate::{Segment, SegmentId, SegmentTrait, M1, M2};
+
+/// Returns the height of the paraxial focus in the OSS for the M1 radius of curvature `r1`
+/// and the M2 height `h2`
+fn paraxial_focus(r1: f64, h2: f64) -> f64 {
+    let h1 = Segment::<M1>::from_id(SegmentId::CENTER).height();
+    let r2 = Segment::<M2>::from_id(SegmentId::CENTER).conic().radius;
+    // M1 focus with respect to the M2 vertex
+    let s = h1 + 0.5 * r1 - h2;
+    h2 + 1. / (2. / r2 - 1. / s)
+}
+
+/// Focus change from the M2 displacement along the OSS z-axis
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Focus {
+    /// M2 displacement along the OSS z-axis \[m\]
+    pub m2: f64,
+}
+impl Focus {
+    /// Creates a new focus change from the M2 displacement `m2` along the OSS z-axis \[m\]
+    pub fn new(m2: f64) -> Self {
+        Self { m2 }
+    }
+    /// Returns the height of the paraxial focus in the OSS
+    pub fn focal_plane(&self) -> f64 {
+        let m1 = Segment::<M1>::from_id(SegmentId::CENTER);
+        let m2 = Segment::<M2>::from_id(SegmentId::CENTER);
+        paraxial_focus(m1.conic().radius, m2.height() + self.m2)
+    }
+    /// Returns the displacement of the paraxial focus along the OSS z-axis \[m\]
+    pub fn shift(&self) -> f64 {
+        self.focal_plane() - Focus::default().focal_plane()
+    }
+    /// Returns the strain of the M1 radius of curvature with the same focus shift
+    pub fn m1_strain(&self) -> f64 {
+        let m1 = Segment::<M1>::from_id(SegmentId::CENTER);
+        let m2 = Segment::<M2>::from_id(SegmentId::CENTER);
+        let (h1, r1) = (m1.height(), m1.conic().radius);
+        let (h2, r2) = (m2.height(), m2.conic().radius);
+        // M1 focus with respect to the M2 vertex imaged onto the focal plane
+        let s = 1. / (2. / r2 - 1. / (self.focal_plane() - h2));
+        2. * (h2 + s - h1) / r1 - 1.
+    }
+}
+
+/// Segment geometry of the focus mode
+pub trait Refocus {
+    /// Returns the segment geometry for the `focus` change
+    fn refocus(&self, focus: &Focus) -> Self;
+}
+impl Refocus for Segment<M1> {
+    /// Refocuses a [`M1`] [`Segment`], the M1 radius of curvature and the distance of an outer
+    /// segment to the OSS z-axis scale with [`Focus::m1_strain`]
+    fn refocus(&self, focus: &Focus) -> Self {
+        let strain = focus.m1_strain();
+        self.rescale(self.height(), strain, strain)
+    }
+}
+impl Refocus for Segment<M2> {
+    /// Refocuses a [`M2`] [`Segment`], the M2 height is shifted by the M2 displacement
+    ///
+    /// An outer segment origin moves on the shifted parent conic along the line joining
+    /// the nominal segment origin to the M1 paraxial focus
+    fn refocus(&self, focus: &Focus) -> Self {
+        let height = self.height() + focus.m2;
+        let strain = self.distance().map_or(0., |d| {
+            let m1 = Segment::<M1>::from_id(SegmentId::CENTER);
+            let f1 = m1.height() + 0.5 * m1.conic().radius;
+            let z = self.translation()[2];
+            let rho = (0..10).fold(d, |rho, _| {
+                d * (f1 - height - self.conic().height(rho)) / (f1 - z)
+            });
+            rho / d - 1.
+        });
+        self.rescale(height, strain, 0.)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Refocus + Clone,
+{
+    /// Returns the 7 segment rigid body motions that move the nominal segments onto the
+    /// segments of the `focus` change
+    pub fn focus_rigidbodymotions(focus: &Focus) -> Vec<Rbm> {
+        Self::reshape_2_rigidbodymotions(|segment| segment.refocus(focus))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{Transform, Vector};
+
+    #[test]
+    fn nominal() {
+        let focus = Focus::default();
+        assert_eq!(focus.shift(), 0.);
+        assert!(focus.m1_strain().abs() < 1e-15);
+        for rbm in Mirror::<M1>::focus_rigidbodymotions(&focus)
+            .into_iter()
+            .chain(Mirror::<M2>::focus_rigidbodymotions(&focus))
+        {
+            assert!(<[f64; 6]>::from(rbm).iter().all(|x| x.abs() < 1e-14));
+        }
+        // the Gregorian focus is below M1
+        assert!(focus.focal_plane() < Segment::<M1>::from_id(SegmentId::CENTER).height());
+    }
+
+    #[test]
+    fn m2_shift() {
+        let focus = Focus::new(1e-4);
+        assert!(focus.shift().abs() > 1e-3);
+        let m1 = Segment::<M1>::from_id(SegmentId::CENTER);
+        let f1 = Vector::from([0., 0., m1.height() + 0.5 * m1.conic().radius]);
+        let rbm = Mirror::<M2>::focus_rigidbodymotions(&focus);
+        // the center segment is pistoned
+        let t = Vector::from(rbm[6].translation).vtov(Mirror::<M2>::center_segment());
+        assert!((t - Vector::from([0., 0., 1e-4])).norm() < 1e-12);
+        assert!(rbm[6].rotation.iter().all(|x| x.abs() < 1e-14));
+        for (segment, rbm) in Mirror::<M2>::outer_segments().zip(rbm) {
+            let refocused = segment.refocus(&focus);
+            // the refocused segment origin is on the shifted parent conic and in the beam
+            let o = refocused.translation();
+            assert!((o[2] - refocused.parent_surface(o[0], o[1])).abs() < 1e-12);
+            let (u, v) = (o - &f1, segment.translation() - &f1);
+            assert!(u.cross(&v).norm() < 1e-12 * v.norm(), "{}", segment.id());
+            assert!(refocused.distance() != segment.distance());
+            assert!(rbm.rotation.iter().any(|x| x.abs() > 1e-8));
+        }
+    }
+
+    /// Returns the height where the ray parallel to the OSS z-axis reflected at the segment
+    /// origin crosses the OSS z-axis
+    fn chief_ray(segment: &Segment<M1>) -> f64 {
+        let o = segment.translation();
+        let n = Vector::from([0., 0., 1.]).vtov(segment.clone());
+        let d = Vector::from([0., 0., -1.]);
+        let r = d.clone() - &n * (2. * d.dot(&n));
+        let rho = (o[0] * o[0] + o[1] * o[1]).sqrt();
+        let dr = (r[0] * o[0] + r[1] * o[1]) / rho;
+        o[2] - r[2] * rho / dr
+    }
+
+    #[test]
+    fn m1_equivalent() {
+        let focus = Focus::new(-5e-5);
+        let m1 = Segment::<M1>::from_id(SegmentId::CENTER);
+        let r1 = m1.conic().radius * (1. + focus.m1_strain());
+        let m2 = Segment::<M2>::from_id(SegmentId::CENTER);
+        let h2 = m2.height();
+        assert!((paraxial_focus(r1, h2) - focus.focal_plane()).abs() < 1e-9);
+        let rbm = Mirror::<M1>::focus_rigidbodymotions(&focus);
+        assert!(<[f64; 6]>::from(rbm[6]).iter().all(|x| x.abs() < 1e-15));
+        let f1 = m1.height() + 0.5 * m1.conic().radius;
+        // M2 image of a point of the OSS z-axis
+        let image = |z: f64| h2 + 1. / (2. / m2.conic().radius - 1. / (z - h2));
+        for (segment, rbm) in Mirror::<M1>::outer_segments().zip(rbm) {
+            let refocused = segment.refocus(&focus);
+            // the refocused segment origin is on the new parent conic, scaled about the vertex
+            let o = refocused.translation();
+            assert!((o[2] - refocused.parent_surface(o[0], o[1])).abs() < 1e-12);
+            let d = segment.distance().unwrap() * (1. + focus.m1_strain());
+            assert!((refocused.distance().unwrap() - d).abs() < 1e-12);
+            assert!(rbm.rotation.iter().all(|x| x.abs() < 1e-14));
+            // the chief ray crossing moves with the M1 focus and reproduces the focus shift
+            let dz = chief_ray(&refocused) - chief_ray(&segment);
+            let shift = image(f1 + dz) - image(f1);
+            assert!(
+                ((shift - focus.shift()) / focus.shift()).abs() < 1e-3,
+                "{}: {} {}",
+                segment.id(),
+                shift,
+                focus.shift()
+            );
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn m1_edge_steps() {
+        let rbm = Rbm::to_mirror(&Mirror::<M1>::focus_rigidbodymotions(&Focus::new(1e-5)));
+        let amplitude = rbm.iter().fold(0f64, |a, x| a.max(x.abs()));
+        // the edge sensors are blind to the M1 focus mode, to first order
+        for edge in Mirror::<M1>::edges(&rbm).unwrap() {
+            assert!(edge.step.abs() < 1e-3 * amplitude, "{:?}", edge);
+        }
+        // but not to a segment piston of the same amplitude
+        let mut piston = vec![0f64; 42];
+        piston[2] = amplitude;
+        let edges = Mirror::<M1>::edges(&piston).unwrap();
+        assert!(edges[0].step.abs() > 0.9 * amplitude);
+    }
+}
@@ -0,0 +1,126 @@
+//! Conversions to and from the [nalgebra](https://docs.rs/nalgebra) and [glam](https://docs.rs/glam) types
+//!
+//! The conversions are enabled with the `nalgebra` and `glam` features.
+//! The 3D vector and point types implement [`Transform`](crate::Transform).
+
+#[cfg(feature = "nalgebra")]
+mod na {
+    use nalgebra::{Point3, Quaternion as NaQuaternion, UnitQuaternion, Vector3};
+
+    use crate::{Quaternion, Transform, Vector};
+
+    impl From<Vector3<f64>> for Vector {
+        fn from(v: Vector3<f64>) -> Self {
+            Vector::from([v.x, v.y, v.z])
+        }
+    }
+    impl From<Vector> for Vector3<f64> {
+        fn from(v: Vector) -> Self {
+            Vector3::new(v[0], v[1], v[2])
+        }
+    }
+    impl From<Point3<f64>> for Vector {
+        fn from(p: Point3<f64>) -> Self {
+            Vector::from([p.x, p.y, p.z])
+        }
+    }
+    impl From<Vector> for Point3<f64> {
+        fn from(v: Vector) -> Self {
+            Point3::new(v[0], v[1], v[2])
+        }
+    }
+    impl From<UnitQuaternion<f64>> for Quaternion {
+        fn from(q: UnitQuaternion<f64>) -> Self {
+            Quaternion::from_parts(q.w, Vector::from([q.i, q.j, q.k]))
+        }
+    }
+    impl From<Quaternion> for UnitQuaternion<f64> {
+        /// Converts a [`Quaternion`] into a [`UnitQuaternion`], normalizing it
+        fn from(q: Quaternion) -> Self {
+            let v = q.vector_as_slice();
+            UnitQuaternion::from_quaternion(NaQuaternion::new(q.scalar(), v[0], v[1], v[2]))
+        }
+    }
+    impl Transform for Vector3<f64> {}
+    impl Transform for Point3<f64> {}
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{Segment, SegmentTrait, M1};
+
+        #[test]
+        fn nalgebra_transform() {
+            let segment = Segment::<M1>::new(2).unwrap();
+            let p = Point3::new(0.1, -0.2, 0.3);
+            let v = Vector::from([0.1, -0.2, 0.3]);
+            assert_eq!(Vector::from(p.to(segment.clone())), v.to(segment.clone()));
+            let q = segment.rotation().unwrap();
+            let u: Vector3<f64> = Vector3::new(0.1, -0.2, 0.3).vtov(segment.clone());
+            let uq = UnitQuaternion::from(q) * Vector3::new(0.1, -0.2, 0.3);
+            assert!((u - uq).norm() < 1e-12);
+        }
+    }
+}
+
+#[cfg(feature = "glam")]
+mod gl {
+    use glam::{DQuat, DVec3, Vec3};
+
+    use crate::{Quaternion, Transform, Vector};
+
+    impl From<DVec3> for Vector {
+        fn from(v: DVec3) -> Self {
+            Vector::from(v.to_array())
+        }
+    }
+    impl From<Vector> for DVec3 {
+        fn from(v: Vector) -> Self {
+            DVec3::new(v[0], v[1], v[2])
+        }
+    }
+    impl From<Vec3> for Vector<f32> {
+        fn from(v: Vec3) -> Self {
+            Vector::from(v.to_array())
+        }
+    }
+    impl From<Vector<f32>> for Vec3 {
+        fn from(v: Vector<f32>) -> Self {
+            Vec3::new(v[0], v[1], v[2])
+        }
+    }
+    impl From<DQuat> for Quaternion {
+        fn from(q: DQuat) -> Self {
+            Quaternion::from_parts(q.w, Vector::from([q.x, q.y, q.z]))
+        }
+    }
+    impl From<Quaternion> for DQuat {
+        /// Converts a [`Quaternion`] into a [`DQuat`], normalizing it
+        fn from(q: Quaternion) -> Self {
+            let v = q.vector_as_slice();
+            DQuat::from_xyzw(v[0], v[1], v[2], q.scalar()).normalize()
+        }
+    }
+    impl Transform for DVec3 {}
+    impl Transform<f32> for Vec3 {}
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{Segment, SegmentTrait, M2};
+
+        #[test]
+        fn glam_transform() {
+            let segment = Segment::<M2>::new(4).unwrap();
+            let u = DVec3::new(0.1, -0.2, 0.3);
+            let v = u.to(segment.clone()).fro(segment.clone());
+            assert!((u - v).length() < 1e-12);
+            let q = DQuat::from(segment.rotation().unwrap());
+            let w: DVec3 = u.vtov(segment.clone());
+            assert!((q * u - w).length() < 1e-12);
+            assert_eq!(Quaternion::from(q), segment.rotation().unwrap());
+            let u32 = Vec3::new(0.1, -0.2, 0.3).to(segment.clone());
+            assert!((u32.as_dvec3() - u.to(segment)).length() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Jacobians of the segment rigid body motions
+//!
+//! The Jacobians are computed analytically.
+//! The Jacobians of the segment points are 3x6 matrices with respect to the rigid body motions
+//! `[Tx,Ty,Tz,Rx,Ry,Rz]` of the segment.
+//! The Jacobians of the mirror rigid body motions are the linearizations about the aligned mirror,
+//! stored row-major with 42 rows.
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+use crate::linalg::{elementary, matmul, matvec};
+use crate::{Gmt, Rbm, Segment, SegmentTrait, Vector};
+#[cfg(feature = "alloc")]
+use crate::{Mirror, Transform};
+
+impl Rbm {
+    /// Returns the 3x6 Jacobian of [`Rbm::apply`] to the point `v` with respect to `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub fn jacobian(&self, v: &Vector) -> [[f64; 6]; 3] {
+        let [rx, ry, rz] = self.rotation;
+        // R = Rz(rz) Ry(ry) Rx(rx)
+        let (x, dx) = elementary(0, rx);
+        let (y, dy) = elementary(1, ry);
+        let (z, dz) = elementary(2, rz);
+        let d = [
+            matvec(&matmul(&z, &matmul(&y, &dx)), v),
+            matvec(&matmul(&z, &matmul(&dy, &x)), v),
+            matvec(&matmul(&dz, &matmul(&y, &x)), v),
+        ];
+        let mut jacobian = [[0f64; 6]; 3];
+        for (i, row) in jacobian.iter_mut().enumerate() {
+            row[i] = 1f64;
+            for (k, dk) in d.iter().enumerate() {
+                row[k + 3] = dk[i];
+            }
+        }
+        jacobian
+    }
+}
+
+impl<M: Gmt> Segment<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the 3x6 Jacobian of the point `v` of the segment, displaced by the rigid body motions `rbm`
+    /// and transformed into the OSS, with respect to `rbm`
+    ///
+    /// This is the Jacobian of `rbm.apply(v).to(segment)`
+    pub fn rbm_jacobian(&self, v: &Vector, rbm: &Rbm) -> [[f64; 6]; 3] {
+        let r = self.rotation_matrix();
+        let j = rbm.jacobian(v);
+        let mut jacobian = [[0f64; 6]; 3];
+        for (row, ri) in jacobian.iter_mut().zip(&r) {
+            for (k, x) in row.iter_mut().enumerate() {
+                *x = (0..3).map(|l| ri[l] * j[l][k]).sum();
+            }
+        }
+        jacobian
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the 42x6 Jacobian of [`Mirror::global_2_rigidbodymotions`] with respect to
+    /// the global rigid body motions `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub fn global_jacobian() -> Vec<f64> {
+        let v7 = Vector::<f64>::null().to(Self::center_segment());
+        let mut jacobian = vec![0f64; 42 * 6];
+        for (segment, rows) in Self::segments().zip(jacobian.chunks_mut(36)) {
+            let r = segment.rotation_matrix();
+            let d = Vector::null().to(segment.clone()) - &v7;
+            for (k, axis) in [Vector::i(), Vector::j(), Vector::k()].iter().enumerate() {
+                // segment translation: Rᵀτ and Rᵀ(ω×d), segment rotation: Rᵀω
+                let t = axis.cross(&d);
+                for i in 0..3 {
+                    rows[6 * i + k] = r[k][i];
+                    rows[6 * i + k + 3] = (0..3).map(|j| r[j][i] * t[j]).sum();
+                    rows[6 * (i + 3) + k + 3] = (0..3).map(|j| r[j][i] * axis[j]).sum();
+                }
+            }
+        }
+        jacobian
+    }
+    /// Returns the 42x2 Jacobian of [`Mirror::tiptilt_2_rigidbodymotions`] with respect to `(tip, tilt)`
+    pub fn tiptilt_jacobian() -> Vec<f64> {
+        Self::global_jacobian()
+            .chunks(6)
+            .flat_map(|row| [row[3], row[4]])
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    fn tiptilt_jacobian<M: Gmt>()
+    where
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let jacobian = Mirror::<M>::tiptilt_jacobian();
+        let h = 1e-7;
+        for k in 0..2 {
+            let mut tt = [0f64; 2];
+            tt[k] = h;
+            let p = Mirror::<M>::tiptilt_2_rigidbodymotions((tt[0], tt[1]));
+            let m = Mirror::<M>::tiptilt_2_rigidbodymotions((-tt[0], -tt[1]));
+            for i in 0..42 {
+                let fd = (p[i] - m[i]) / (2. * h);
+                assert!(
+                    (fd - jacobian[i * 2 + k]).abs() < 1e-6,
+                    "{}: {} {}",
+                    i,
+                    fd,
+                    jacobian[i * 2 + k]
+                );
+            }
+        }
+    }
+    #[test]
+    fn tiptilt_jacobian_m1() {
+        tiptilt_jacobian::<M1>();
+    }
+    #[test]
+    fn tiptilt_jacobian_m2() {
+        tiptilt_jacobian::<M2>();
+    }
+
+    #[test]
+    fn global_jacobian() {
+        let jacobian = Mirror::<M2>::global_jacobian();
+        let h = 1e-7;
+        for k in 0..6 {
+            let mut g = [0f64; 6];
+            g[k] = h;
+            let p = Rbm::to_mirror(&Mirror::<M2>::global_2_rigidbodymotions(&Rbm::from(g)));
+            g[k] = -h;
+            let m = Rbm::to_mirror(&Mirror::<M2>::global_2_rigidbodymotions(&Rbm::from(g)));
+            for i in 0..42 {
+                let fd = (p[i] - m[i]) / (2. * h);
+                assert!((fd - jacobian[i * 6 + k]).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn rbm_jacobian() {
+        let v = Vector::from([1.2, -0.7, 0.05]);
+        let rbm = Rbm::new([1e-3, -2e-3, 5e-4], [0.1, -0.2, 0.3]);
+        let h = 1e-6;
+        for segment in Mirror::<M1>::segments() {
+            let jacobian = segment.rbm_jacobian(&v, &rbm);
+            for k in 0..6 {
+                let mut d: [f64; 6] = rbm.into();
+                d[k] += h;
+                let p = Rbm::from(d).apply(v.clone()).to(segment.clone());
+                d[k] -= 2. * h;
+                let m = Rbm::from(d).apply(v.clone()).to(segment.clone());
+                for i in 0..3 {
+                    let fd = (p[i] - m[i]) / (2. * h);
+                    assert!(
+                        (fd - jacobian[i][k]).abs() < 1e-8,
+                        "{} {}",
+                        fd,
+                        jacobian[i][k]
+                    );
+                }
+            }
+        }
+    }
+}
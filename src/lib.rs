@@ -1,32 +1,225 @@
 //! Geometric transformation for the GMT segmented mirrors
+//!
+//! The core geometry ([`Vector`], [`Quaternion`], [`Conic`], [`Segment`] and [`Transform`])
+//! is `no_std` compatible: disable the default `std` feature to use it on embedded targets.
+//! The `alloc` feature enables the allocation-based APIs like the `Vec<f64>` transforms,
+//! the mirror rigid body motions vectors and the segment outlines.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod aperture;
+#[cfg(feature = "std")]
+pub mod basis;
+#[cfg(feature = "std")]
+pub mod compensation;
+pub mod covariance;
+mod dual_quaternion;
+#[cfg(feature = "std")]
+mod edges;
+#[cfg(feature = "capi")]
+pub mod ffi;
+pub mod focus;
+#[cfg(any(feature = "nalgebra", feature = "glam"))]
+mod interop;
+mod jacobian;
+mod linalg;
+#[cfg(feature = "std")]
+pub mod modes;
+#[cfg(feature = "std")]
+pub mod montecarlo;
+#[cfg(feature = "std")]
+pub mod npy;
+#[cfg(feature = "std")]
+pub mod nullspace;
+#[cfg(feature = "std")]
+pub mod pointcloud;
 mod quaternion;
+mod rbm;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+mod sampling;
+mod se3;
 mod segment;
+mod segment_id;
+#[cfg(feature = "std")]
+pub mod statistics;
+pub mod thermal;
+#[cfg(feature = "std")]
+pub mod timeseries;
 mod transform;
 mod vector;
 
-use std::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec, vec::Vec};
+use core::{fmt, marker::PhantomData};
+// the float methods are inherent if a dependency (or the test harness) links `std`
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
 
+pub use aperture::Aperture;
+#[cfg(feature = "alloc")]
+pub use aperture::Outline;
+#[cfg(feature = "std")]
+pub use basis::{ModalBasis, Polynomials};
+#[cfg(feature = "std")]
+pub use compensation::{Alignment, Metrics, Objective};
+pub use covariance::{UncertainRbm, UncertainVector};
+pub use dual_quaternion::DualQuaternion;
+#[cfg(feature = "std")]
+pub use edges::Edge;
+pub use focus::{Focus, Refocus};
+#[cfg(feature = "std")]
+pub use modes::{Dof, Mode, Modes, RbmBasis};
+#[cfg(feature = "std")]
+pub use nullspace::{InteractionMatrix, NullSpace};
+#[cfg(feature = "std")]
+pub use pointcloud::{Frame, PointCloud};
 pub use quaternion::Quaternion;
+pub use rbm::Rbm;
+#[cfg(feature = "std")]
+pub use sampling::{Samples, Sampling};
+pub use se3::Se3;
 pub use segment::{Segment, SegmentTrait};
+pub use segment_id::SegmentId;
+pub use thermal::{Cte, Expansion, ThermalExpansion};
+#[cfg(feature = "std")]
+pub use timeseries::{Interpolation, RbmSeries};
 pub use transform::{Transform, TransformMut};
 pub use vector::Vector;
 
+/// Floating point types of [`Vector`] and [`Quaternion`]
+///
+/// The conversions between the floating point types go through `f64`
+pub trait Real:
+    num_traits::Float
+    + num_traits::FloatConst
+    + Into<f64>
+    + fmt::Debug
+    + fmt::Display
+    + Default
+    + 'static
+{
+    /// Converts a `f64` to the nearest value of this type
+    fn from_f64(x: f64) -> Self;
+}
+impl Real for f32 {
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+}
+impl Real for f64 {
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("The segment id {0} is not in the range [1,7]")]
     SegmentId(i32),
+    #[error("The interaction matrix row length {0} does not match the {1} rigid body motions")]
+    InteractionRow(usize, usize),
+    #[error("The interaction matrix has no rigid body motions")]
+    EmptyInteraction,
+    #[error("The surface length {0} does not match the {1} samples")]
+    SurfaceLength(usize, usize),
+    #[error("The {0} samples are fewer than the {1} polynomials")]
+    Undersampled(usize, usize),
+    #[error("The rigid body motions length {0} is not {1}")]
+    RbmLength(usize, usize),
+    #[error("The vector length {0} is neither 2 nor 3")]
+    VectorLength(usize),
+    #[error("The quaternion length {0} is not 4")]
+    QuaternionLength(usize),
+    #[error("Non-finite coordinate")]
+    NonFinite,
+    #[error("The rotation quaternion norm {0} is not 1")]
+    NonUnitRotation(f64),
+    #[error("The rotation axis is the null vector")]
+    ZeroAxis,
+    #[error("Invalid prescription: {0}")]
+    Prescription(&'static str),
+    #[error("The matrix length {0} does not match {1}")]
+    MatrixLength(usize, usize),
+    #[error(
+        "The correlation matrix is not a symmetric positive definite matrix with a unit diagonal"
+    )]
+    Correlation,
+    #[error("Invalid time series: {0}")]
+    TimeSeries(&'static str),
+    #[error("Invalid tolerance: {0}")]
+    Tolerance(&'static str),
+    #[error("Singular sensitivity matrix")]
+    Singular,
+    #[error("No convergence, the residual norm is {0}")]
+    Convergence(f64),
+    #[cfg(feature = "alloc")]
+    #[error("Unknown coordinate frame {0}, expected OSS, M1S# or M2S#")]
+    Frame(String),
+    #[cfg(feature = "alloc")]
+    #[error("Unknown length unit {0}, expected m or mm")]
+    Unit(String),
+    #[cfg(feature = "alloc")]
+    #[error("Point cloud line {0}: {1}")]
+    PointCloud(usize, String),
+    #[cfg(feature = "alloc")]
+    #[error("NumPy file: {0}")]
+    Npy(String),
+    #[cfg(feature = "std")]
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+impl From<core::convert::Infallible> for Error {
+    fn from(e: core::convert::Infallible) -> Self {
+        match e {}
+    }
 }
 
 /// Conic surface
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "ConicRepr"))]
 pub struct Conic {
     /// Radius of curvature
     radius: f64,
     /// Conic constant
     constant: f64,
 }
+/// Deserialization representation of a [`Conic`], validated with [`Conic::new`]
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Conic", deny_unknown_fields)]
+struct ConicRepr {
+    radius: f64,
+    constant: f64,
+}
+#[cfg(feature = "serde")]
+impl core::convert::TryFrom<ConicRepr> for Conic {
+    type Error = Error;
+    fn try_from(repr: ConicRepr) -> Result<Self, Self::Error> {
+        Conic::new(repr.radius, repr.constant)
+    }
+}
 impl Conic {
+    /// Creates a new conic surface from the radius of curvature and the conic constant
+    ///
+    /// The radius of curvature must be finite and non-zero and the conic constant finite
+    pub fn new(radius: f64, constant: f64) -> Result<Self, Error> {
+        if !(radius.is_finite() && radius != 0f64) {
+            return Err(Error::Prescription(
+                "the radius of curvature must be finite and non-zero",
+            ));
+        }
+        if !constant.is_finite() {
+            return Err(Error::Prescription("the conic constant must be finite"));
+        }
+        Ok(Self { radius, constant })
+    }
     /// GMT M1 conic surface
     pub fn m1() -> Self {
         Self {
@@ -47,6 +240,11 @@ impl Conic {
         let rho2 = rho * rho;
         self.radius.signum() * rho2 / (c + (c * c - (self.constant + 1f64) * rho2).sqrt())
     }
+    /// Conic surface slope
+    pub fn slope(&self, rho: f64) -> f64 {
+        let c = self.radius.abs();
+        self.radius.signum() * rho / (c * c - (self.constant + 1f64) * rho * rho).sqrt()
+    }
 }
 
 /// Type representing the GMT primary mirror
@@ -57,9 +255,16 @@ pub struct M1;
 pub struct M2;
 
 /// GMT optics interface
-pub trait Gmt {}
-impl Gmt for M1 {}
-impl Gmt for M2 {}
+pub trait Gmt {
+    /// Mirror name
+    const NAME: &'static str = "GMT";
+}
+impl Gmt for M1 {
+    const NAME: &'static str = "M1";
+}
+impl Gmt for M2 {
+    const NAME: &'static str = "M2";
+}
 
 /// GMT mirrors
 pub struct Mirror<M: Gmt>(PhantomData<M>);
@@ -68,16 +273,16 @@ where
     Segment<M>: SegmentTrait + Clone,
 {
     /// Returns the segment rigid body motions for a mirror tip-tilt
+    #[cfg(feature = "alloc")]
     pub fn tiptilt_2_rigidbodymotions((tip, tilt): (f64, f64)) -> Vec<f64> {
-        let q_tt = Quaternion::unit(tip, Vector::i()) * Quaternion::unit(tilt, Vector::j());
-        let v7 = Vector::null().to(<Segment<M> as SegmentTrait>::new(7).unwrap());
+        let q_tt =
+            Quaternion::rotation(tip, &Vector::i()) * Quaternion::rotation(tilt, &Vector::j());
+        let v7 = Vector::null().to(Self::center_segment());
         let mut rbm = vec![];
-        for sid in 1..=7 {
-            let segment = <Segment<M> as SegmentTrait>::new(sid).unwrap();
-
+        for segment in Self::segments() {
             let v = Vector::null().to(segment.clone()) - &v7;
             let vp = &q_tt * v * q_tt.complex_conjugate();
-            let vs = (Vector::from(vp.vector_as_slice()) + &v7).fro(segment.clone());
+            let vs = (vp.vector() + &v7).fro(segment.clone());
             rbm.extend(vs);
 
             let (r, p, y) = if let Some(q_s) = segment.rotation() {
@@ -92,7 +297,7 @@ where
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     #[test]
@@ -139,6 +344,56 @@ mod tests {
         }
     }
     #[test]
+    fn transform_f32_tofro() {
+        for sid in 1..=7 {
+            let segment = Segment::<M2>::new(sid).unwrap();
+            let u = [0.1f32, 0.1, 0.];
+            let v = u.to(segment.clone());
+            let w = [0.1f64, 0.1, 0.].to(segment.clone());
+            for (v, w) in v.iter().zip(w.iter()) {
+                assert!((*v as f64 - w).abs() < 1e-5);
+            }
+            let v = v.fro(segment);
+            for (u, v) in u.iter().zip(v.iter()) {
+                assert!((u - v).abs() < 1e-5);
+            }
+        }
+    }
+    #[test]
+    fn transform_vec() {
+        for sid in 1..=7 {
+            let segment = Segment::<M2>::new(sid).unwrap();
+            let v = vec![0.1f64, 0.1].try_to(segment.clone()).unwrap();
+            assert_eq!(v, Vec::from([0.1f64, 0.1, 0.].to(segment.clone())));
+            let u = v.try_fro(segment.clone()).unwrap();
+            assert!(u
+                .iter()
+                .zip([0.1, 0.1, 0.])
+                .all(|(u, v)| (u - v).abs() < 1e-12));
+        }
+    }
+    #[test]
+    fn transform_try() {
+        let segment = Segment::<M1>::new(3).unwrap();
+        let v = vec![0.1f64, -0.2, 0.3];
+        assert_eq!(
+            v.clone().try_to(segment.clone()).unwrap(),
+            Vec::from([0.1f64, -0.2, 0.3].to(segment.clone()))
+        );
+        assert_eq!(
+            [0.1f64, -0.2, 0.3].try_vfrov(segment.clone()).unwrap(),
+            [0.1f64, -0.2, 0.3].vfrov(segment.clone())
+        );
+        assert!(matches!(
+            vec![0.1f64].try_fro(segment.clone()),
+            Err(Error::VectorLength(1))
+        ));
+        assert!(matches!(
+            vec![0.1, f64::NAN, 0.].try_vtov(segment),
+            Err(Error::NonFinite)
+        ));
+    }
+    #[test]
     fn transform_m2_to() {
         for sid in 1..=7 {
             let u = [0.1f64, 0.1, 0.];
@@ -159,29 +414,29 @@ mod tests {
     #[test]
     fn global_tiptilt_o() {
         let v7 = Vector::null().to(Segment::<M1>::new(7).unwrap());
-        let q = Quaternion::unit(10f64.to_radians(), Vector::i());
+        let q = Quaternion::unit(10f64.to_radians(), Vector::i()).unwrap();
         for sid in 1..=7 {
             let segment = Segment::<M1>::new(sid).unwrap();
             let v = Vector::null().to(segment.clone()) - &v7;
             let vp = &q * Quaternion::from(v) * q.complex_conjugate();
-            let vs = (Vector::from(vp.vector_as_slice()) + &v7).fro(segment);
+            let vs = (vp.vector() + &v7).fro(segment);
             println!("M2S{} - v: {:#?}", sid, vs);
         }
     }
     #[test]
     fn global_tiptilt_ijk() {
-        let q = Quaternion::unit(10f64.to_radians(), Vector::i());
+        let q = Quaternion::unit(10f64.to_radians(), Vector::i()).unwrap();
         for sid in 1..=7 {
             let segment = Segment::<M1>::new(sid).unwrap();
             let v = Vector::k().vtov(segment.clone());
             let vp = &q * Quaternion::from(v) * q.complex_conjugate();
-            let vs = Vector::from(vp.vector_as_slice()).vfrov(segment);
+            let vs = vp.vector().vfrov(segment);
             println!("M2S{} - v: {:#?}", sid, vs);
         }
     }
     #[test]
     fn global_tiptilt() {
-        let q_tt = Quaternion::unit(10f64.to_radians(), Vector::j());
+        let q_tt = Quaternion::unit(10f64.to_radians(), Vector::j()).unwrap();
         for sid in 1..=7 {
             let segment = Segment::<M1>::new(sid).unwrap();
             if let Some(q_s) = segment.rotation() {
@@ -227,4 +482,32 @@ mod tests {
             )
         });
     }
+    #[test]
+    fn tiptilt_composition() {
+        let (tip, tilt) = (1f64.to_radians(), -2.5f64.to_radians());
+        let q_tt = Quaternion::unit(tip, Vector::i()).unwrap()
+            * Quaternion::unit(tilt, Vector::j()).unwrap();
+        let v7 = Vector::null().to(Mirror::<M1>::center_segment());
+        let rbm = Mirror::<M1>::tiptilt_2_rigidbodymotions((tip, tilt));
+        for (segment, rbm) in Mirror::<M1>::segments().zip(Rbm::from_mirror(&rbm).unwrap()) {
+            // a segment point moved by the segment rigid body motions and by the mirror tip-tilt
+            let v = Vector::from([0.1, -0.2, 0.05]);
+            let u = rbm.apply(v.clone()).to(segment.clone());
+            let w = (&q_tt * (v.to(segment) - &v7) * q_tt.complex_conjugate()).vector() + &v7;
+            assert!((u - w).norm() < 1e-12);
+        }
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn conic_serde() {
+        let json = serde_json::to_string(&Conic::m1()).unwrap();
+        assert_eq!(serde_json::from_str::<Conic>(&json).unwrap(), Conic::m1());
+        for json in [
+            r#"{"radius":0.0,"constant":-1.0}"#,
+            r#"{"radius":36.0}"#,
+            r#"{"radius":36.0,"constant":-1.0,"k":0.0}"#,
+        ] {
+            assert!(serde_json::from_str::<Conic>(json).is_err(), "{}", json);
+        }
+    }
 }
@@ -0,0 +1,231 @@
+//! Dense linear algebra helpers
+//!
+//! The small matrices are row-major `N`x`N` arrays, the other matrices are stored row-major
+//! in a flat `Vec<f64>`
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+/// Returns the matrix product `a b`
+pub(crate) fn matmul<const N: usize>(a: &[[f64; N]; N], b: &[[f64; N]; N]) -> [[f64; N]; N] {
+    let mut c = [[0f64; N]; N];
+    for (ci, ai) in c.iter_mut().zip(a) {
+        for (j, cij) in ci.iter_mut().enumerate() {
+            *cij = (0..N).map(|k| ai[k] * b[k][j]).sum();
+        }
+    }
+    c
+}
+/// Returns the product `a v` of the matrix `a` with the vector `v`
+pub(crate) fn matvec<const N: usize>(a: &[[f64; N]; N], v: &[f64]) -> [f64; N] {
+    let mut u = [0f64; N];
+    u.iter_mut()
+        .zip(a)
+        .for_each(|(u, a)| *u = a.iter().zip(v).map(|(a, v)| a * v).sum());
+    u
+}
+/// Returns the transpose of the matrix `a`
+pub(crate) fn transpose<const N: usize>(a: &[[f64; N]; N]) -> [[f64; N]; N] {
+    let mut t = *a;
+    for (i, row) in a.iter().enumerate() {
+        for (j, &x) in row.iter().enumerate() {
+            t[j][i] = x;
+        }
+    }
+    t
+}
+/// Returns the elementary rotation matrix of angle `o` about the axis `axis` (0:x, 1:y, 2:z)
+/// and its derivative
+pub(crate) fn elementary(axis: usize, o: f64) -> ([[f64; 3]; 3], [[f64; 3]; 3]) {
+    let (s, c) = o.sin_cos();
+    let (i, j) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (mut r, mut dr) = ([[0f64; 3]; 3], [[0f64; 3]; 3]);
+    r[axis][axis] = 1f64;
+    r[i][i] = c;
+    r[i][j] = -s;
+    r[j][i] = s;
+    r[j][j] = c;
+    dr[i][i] = -s;
+    dr[i][j] = -c;
+    dr[j][i] = c;
+    dr[j][j] = -s;
+    (r, dr)
+}
+
+#[cfg(feature = "std")]
+/// Returns the singular values and the right singular vectors of the `m`x`n` matrix `a`
+///
+/// The `n` singular values are sorted in decreasing order, the right singular vectors are
+/// returned in the same order (one-sided Jacobi algorithm)
+pub(crate) fn svd(a: &[f64], m: usize, n: usize) -> (Vec<f64>, Vec<Vec<f64>>) {
+    // columns of a and v
+    let mut u: Vec<Vec<f64>> = (0..n)
+        .map(|j| (0..m).map(|i| a[i * n + j]).collect())
+        .collect();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|j| (0..n).map(|i| if i == j { 1f64 } else { 0f64 }).collect())
+        .collect();
+    for _ in 0..100 {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let alpha = dot(&u[p], &u[p]);
+                let beta = dot(&u[q], &u[q]);
+                let gamma = dot(&u[p], &u[q]);
+                if gamma == 0f64 || gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2f64 * gamma);
+                let t = if zeta == 0f64 {
+                    1f64
+                } else {
+                    zeta.signum() / (zeta.abs() + (zeta * zeta + 1f64).sqrt())
+                };
+                let c = 1f64 / (t * t + 1f64).sqrt();
+                let s = c * t;
+                for w in [&mut u, &mut v] {
+                    let (head, tail) = w.split_at_mut(q);
+                    head[p]
+                        .iter_mut()
+                        .zip(tail[0].iter_mut())
+                        .for_each(|(x, y)| {
+                            let (xp, yq) = (*x, *y);
+                            *x = c * xp - s * yq;
+                            *y = s * xp + c * yq;
+                        });
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let sigma: Vec<f64> = u.iter().map(|x| dot(x, x).sqrt()).collect();
+    let mut idx: Vec<usize> = (0..n).collect();
+    idx.sort_by(|&i, &j| sigma[j].total_cmp(&sigma[i]));
+    (
+        idx.iter().map(|&i| sigma[i]).collect(),
+        idx.into_iter().map(|i| v[i].clone()).collect(),
+    )
+}
+
+#[cfg(feature = "std")]
+/// Returns the row-major lower triangular Cholesky factor `l` of the `n`x`n` matrix `a = l l^T`
+///
+/// Returns `None` if `a` is not symmetric positive definite
+pub(crate) fn cholesky(a: &[f64], n: usize) -> Option<Vec<f64>> {
+    let mut l = vec![0f64; n * n];
+    for i in 0..n {
+        for j in 0..=i {
+            if (a[i * n + j] - a[j * n + i]).abs() > 1e-12 * (a[i * n + i] * a[j * n + j]).sqrt() {
+                return None;
+            }
+            let s = a[i * n + j] - dot(&l[i * n..i * n + j], &l[j * n..j * n + j]);
+            if i == j {
+                if s <= 0f64 {
+                    return None;
+                }
+                l[i * n + i] = s.sqrt();
+            } else {
+                l[i * n + j] = s / l[j * n + j];
+            }
+        }
+    }
+    Some(l)
+}
+
+#[cfg(feature = "std")]
+/// Returns the solution `x` of the `n`x`n` linear system `a x = b`
+///
+/// Returns `None` if `a` is singular (Gaussian elimination with partial pivoting)
+pub(crate) fn solve(a: &[f64], b: &[f64], n: usize) -> Option<Vec<f64>> {
+    let mut a = a.to_vec();
+    let mut x = b.to_vec();
+    let scale = a.iter().fold(0f64, |m, x| m.max(x.abs()));
+    for k in 0..n {
+        let p = (k..n).max_by(|&i, &j| a[i * n + k].abs().total_cmp(&a[j * n + k].abs()))?;
+        if a[p * n + k].abs() <= f64::EPSILON * scale * n as f64 {
+            return None;
+        }
+        if p != k {
+            for j in 0..n {
+                a.swap(k * n + j, p * n + j);
+            }
+            x.swap(k, p);
+        }
+        for i in k + 1..n {
+            let f = a[i * n + k] / a[k * n + k];
+            for j in k..n {
+                a[i * n + j] -= f * a[k * n + j];
+            }
+            x[i] -= f * x[k];
+        }
+    }
+    for k in (0..n).rev() {
+        let s: f64 = (k + 1..n).map(|j| a[k * n + j] * x[j]).sum();
+        x[k] = (x[k] - s) / a[k * n + k];
+    }
+    Some(x)
+}
+
+#[cfg(feature = "std")]
+/// Dot product
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).fold(0f64, |s, (x, y)| s + x * y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_matrices() {
+        let a = [[1., 2., 0.], [0., 1., -1.], [3., 0., 1.]];
+        let (r, _) = elementary(2, 0.3);
+        let i = matmul(&r, &transpose(&r));
+        for (k, row) in i.iter().enumerate() {
+            for (l, x) in row.iter().enumerate() {
+                assert!((x - if k == l { 1. } else { 0. }).abs() < 1e-15);
+            }
+        }
+        assert_eq!(matvec(&a, &[1., 1., 1.]), [3., 0., 4.]);
+        assert_eq!(transpose(&a)[0], [1., 0., 3.]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn solve_3x3() {
+        let a = [0., 2., 1., 1., 1., 0., 3., 0., 1.];
+        let x = solve(&a, &[5., 3., 4.], 3).unwrap();
+        assert!(x
+            .iter()
+            .zip([1., 2., 1.])
+            .all(|(x, y)| (x - y).abs() < 1e-12));
+        assert!(solve(&[1., 2., 2., 4.], &[1., 2.], 2).is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn svd_3x2() {
+        let (s, v) = svd(&[3., 0., 0., 4., 0., 0.], 3, 2);
+        assert!((s[0] - 4.).abs() < 1e-12 && (s[1] - 3.).abs() < 1e-12);
+        assert!((v[0][1].abs() - 1.).abs() < 1e-12);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn cholesky_factor() {
+        let a = [4., 2., 0.6, 2., 2., 0.5, 0.6, 0.5, 3.];
+        let l = cholesky(&a, 3).unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!(
+                    (dot(&l[i * 3..i * 3 + 3], &l[j * 3..j * 3 + 3]) - a[i * 3 + j]).abs() < 1e-12
+                );
+            }
+        }
+        assert!(cholesky(&[1., 2., 2., 1.], 2).is_none());
+    }
+}
@@ -0,0 +1,317 @@
+//! Mirror modes in the rigid body motions space
+//!
+//! The modes are vectors of the 42 segment rigid body motions of a mirror,
+//! orthonormalized in the order they are given: a mode that is linearly dependent on
+//! the previous ones is discarded and reported by [`RbmBasis::discarded`].
+//! The coordinates of the rigid body motions in a [`RbmBasis`] are the modal coefficients
+//! returned by [`RbmBasis::project`] and [`RbmBasis::reconstruct`] is the inverse transform
+//! for the rigid body motions in the span of the basis.
+
+use crate::{
+    linalg, nullspace::N_RBM, Error, Gmt, Mirror, Segment, SegmentId, SegmentTrait, Transform,
+    Vector,
+};
+
+/// Rigid body motion degree of freedom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dof {
+    /// Translation along the x-axis
+    Tx,
+    /// Translation along the y-axis
+    Ty,
+    /// Translation along the z-axis
+    Tz,
+    /// Rotation around the x-axis
+    Rx,
+    /// Rotation around the y-axis
+    Ry,
+    /// Rotation around the z-axis
+    Rz,
+}
+impl Dof {
+    /// Iterates over the 6 degrees of freedom in the order `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub fn all() -> impl Iterator<Item = Dof> + Clone {
+        [Dof::Tx, Dof::Ty, Dof::Tz, Dof::Rx, Dof::Ry, Dof::Rz]
+            .iter()
+            .copied()
+    }
+    /// Returns the index of the degree of freedom in `[Tx,Ty,Tz,Rx,Ry,Rz]`
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// Mirror mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Whole mirror rigid body motion in the OSS, see [`Mirror::global_jacobian`]
+    Global(Dof),
+    /// Segment translation along the segment z-axis
+    Piston(SegmentId),
+    /// Segment rotation around the segment x-axis
+    Tip(SegmentId),
+    /// Segment rotation around the segment y-axis
+    Tilt(SegmentId),
+    /// Segment rotation around the segment z-axis
+    Clocking(SegmentId),
+    /// Focus-like radial breathing of the segments, per unit strain
+    ///
+    /// The parent conic is scaled about its vertex: the outer segments slide on the scaled
+    /// parent conic, they move radially and along the OSS z-axis with unchanged orientations
+    Breathing,
+}
+impl Mode {
+    /// Returns the 42 segment rigid body motions of the mode
+    fn rbm<M>(&self) -> Vec<f64>
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let mut rbm = vec![0f64; N_RBM];
+        let mut set = |id: SegmentId, k: usize| rbm[6 * id.index() + k] = 1f64;
+        match *self {
+            Mode::Global(dof) => {
+                let jacobian = Mirror::<M>::global_jacobian();
+                rbm.iter_mut()
+                    .zip(jacobian.chunks(6))
+                    .for_each(|(x, row)| *x = row[dof.index()]);
+            }
+            Mode::Piston(id) => set(id, 2),
+            Mode::Tip(id) => set(id, 3),
+            Mode::Tilt(id) => set(id, 4),
+            Mode::Clocking(id) => set(id, 5),
+            Mode::Breathing => {
+                // the segment origin moves along the line from the parent conic vertex
+                for (x, segment) in rbm.chunks_mut(6).zip(Mirror::<M>::segments()) {
+                    let vertex = Vector::from([0., 0., segment.height()]);
+                    let t = (segment.translation() - vertex).vfrov(segment);
+                    x[..3].copy_from_slice(&t[..]);
+                }
+            }
+        }
+        rbm
+    }
+}
+
+/// Families of mirror modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modes {
+    /// The 6 whole mirror rigid body motions
+    Global,
+    /// The 7 segment pistons
+    Piston,
+    /// The 7 segment tips and the 7 segment tilts
+    TipTilt,
+    /// The 7 segment clockings
+    Clocking,
+    /// The focus-like radial breathing of the segments
+    ///
+    /// The breathing is in the span of the global Tz and of the pistons,
+    /// it must be given before these families to get a breathing coordinate,
+    /// otherwise it is one of the [`RbmBasis::discarded`] modes
+    Breathing,
+}
+impl Modes {
+    /// Returns the modes of the family
+    pub fn modes(&self) -> Vec<Mode> {
+        match self {
+            Modes::Global => Dof::all().map(Mode::Global).collect(),
+            Modes::Piston => SegmentId::all().map(Mode::Piston).collect(),
+            Modes::TipTilt => SegmentId::all()
+                .map(Mode::Tip)
+                .chain(SegmentId::all().map(Mode::Tilt))
+                .collect(),
+            Modes::Clocking => SegmentId::all().map(Mode::Clocking).collect(),
+            Modes::Breathing => vec![Mode::Breathing],
+        }
+    }
+}
+
+/// Orthonormal basis of mirror modes
+#[derive(Debug, Clone)]
+pub struct RbmBasis {
+    /// Modes the basis vectors are derived from
+    labels: Vec<Mode>,
+    /// Orthonormal modes, one mode per row
+    modes: Vec<Vec<f64>>,
+    /// Modes linearly dependent on the previous ones
+    discarded: Vec<Mode>,
+}
+impl RbmBasis {
+    /// Creates the orthonormal basis of the `modes`
+    ///
+    /// The modes that are linearly dependent on the previous ones are left out of the basis
+    /// and are returned by [`RbmBasis::discarded`]
+    pub fn new<M>(modes: &[Mode]) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait + Clone,
+    {
+        let mut basis = Self {
+            labels: vec![],
+            modes: vec![],
+            discarded: vec![],
+        };
+        // modified Gram-Schmidt
+        for mode in modes {
+            let mut rbm = mode.rbm::<M>();
+            let norm0 = linalg::dot(&rbm, &rbm).sqrt();
+            for other in &basis.modes {
+                let c = linalg::dot(&rbm, other);
+                rbm.iter_mut().zip(other).for_each(|(x, o)| *x -= c * o);
+            }
+            let norm = linalg::dot(&rbm, &rbm).sqrt();
+            if norm > 1e-9 * norm0 {
+                rbm.iter_mut().for_each(|x| *x /= norm);
+                basis.labels.push(*mode);
+                basis.modes.push(rbm);
+            } else {
+                basis.discarded.push(*mode);
+            }
+        }
+        basis
+    }
+    /// Returns the modes the basis vectors are derived from
+    pub fn labels(&self) -> &[Mode] {
+        &self.labels
+    }
+    /// Returns the modes that are linearly dependent on the previous ones, in the order they are given
+    pub fn discarded(&self) -> &[Mode] {
+        &self.discarded
+    }
+    /// Returns the orthonormal modes, one mode per row
+    pub fn modes(&self) -> &[Vec<f64>] {
+        &self.modes
+    }
+    /// Returns the number of modes
+    pub fn len(&self) -> usize {
+        self.modes.len()
+    }
+    /// Checks if the basis has no modes
+    pub fn is_empty(&self) -> bool {
+        self.modes.is_empty()
+    }
+    /// Returns the modal coefficients of the 42 segment rigid body motions `rbm`
+    pub fn project(&self, rbm: &[f64]) -> Result<Vec<f64>, Error> {
+        if rbm.len() != N_RBM {
+            return Err(Error::RbmLength(rbm.len(), N_RBM));
+        }
+        Ok(self.modes.iter().map(|m| linalg::dot(m, rbm)).collect())
+    }
+    /// Returns the 42 segment rigid body motions of the modal `coefficients`
+    pub fn reconstruct(&self, coefficients: &[f64]) -> Result<Vec<f64>, Error> {
+        if coefficients.len() != self.len() {
+            return Err(Error::MatrixLength(coefficients.len(), self.len()));
+        }
+        let mut rbm = vec![0f64; N_RBM];
+        for (mode, c) in self.modes.iter().zip(coefficients) {
+            rbm.iter_mut().zip(mode).for_each(|(x, m)| *x += c * m);
+        }
+        Ok(rbm)
+    }
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the orthonormal basis of the families of `modes`, in the order of the families
+    pub fn rbm_basis(modes: &[Modes]) -> RbmBasis {
+        let modes: Vec<Mode> = modes.iter().flat_map(|m| m.modes()).collect();
+        RbmBasis::new::<M>(&modes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rbm, M1, M2};
+
+    #[test]
+    fn orthonormal() {
+        let basis = Mirror::<M1>::rbm_basis(&[
+            Modes::Global,
+            Modes::Piston,
+            Modes::TipTilt,
+            Modes::Clocking,
+            Modes::Breathing,
+        ]);
+        // the 6 outer segments move alike in their radial plane: the breathing is a combination
+        // of the global Tz and of the pistons, 6 + 7 + 14 + 7 modes
+        assert_eq!(basis.len(), 34);
+        assert_eq!(basis.discarded(), &[Mode::Breathing]);
+        for (i, a) in basis.modes().iter().enumerate() {
+            for (j, b) in basis.modes().iter().enumerate() {
+                let d = if i == j { 1. } else { 0. };
+                assert!((linalg::dot(a, b) - d).abs() < 1e-12);
+            }
+        }
+        assert_eq!(basis.labels()[..6], Modes::Global.modes()[..]);
+        // the breathing given first is kept in place of a segment piston
+        let basis = Mirror::<M1>::rbm_basis(&[
+            Modes::Breathing,
+            Modes::Global,
+            Modes::Piston,
+            Modes::TipTilt,
+            Modes::Clocking,
+        ]);
+        assert_eq!(basis.len(), 34);
+        assert_eq!(basis.labels()[0], Mode::Breathing);
+        assert_eq!(basis.discarded().len(), 1);
+        assert!(matches!(basis.discarded()[0], Mode::Piston(_)));
+        assert!(!basis.labels().contains(&basis.discarded()[0]));
+    }
+
+    #[test]
+    fn breathing() {
+        let basis = Mirror::<M1>::rbm_basis(&[
+            Modes::Piston,
+            Modes::TipTilt,
+            Modes::Clocking,
+            Modes::Breathing,
+        ]);
+        assert_eq!(basis.len(), 29);
+        assert_eq!(basis.labels()[28], Mode::Breathing);
+        assert!(basis.discarded().is_empty());
+        // the segments rescaled about the parent conic vertex
+        let strain = 1e-7;
+        let rescaled = Rbm::to_mirror(&Mirror::<M1>::reshape_2_rigidbodymotions(|segment| {
+            segment.rescale(segment.height(), strain, strain)
+        }));
+        let rbm = Mode::Breathing.rbm::<M1>();
+        assert!(rbm
+            .iter()
+            .zip(&rescaled)
+            .all(|(a, b)| (a * strain - b).abs() < 1e-12));
+        // the center segment is not moving
+        assert!(rbm[36..].iter().all(|x| *x == 0.));
+    }
+
+    #[test]
+    fn global_tiptilt() {
+        let basis = Mirror::<M2>::rbm_basis(&[Modes::Global]);
+        assert_eq!(basis.len(), 6);
+        let rbm = Mirror::<M2>::tiptilt_2_rigidbodymotions((1e-7, -2e-7));
+        let c = basis.project(&rbm).unwrap();
+        let other = basis.reconstruct(&c).unwrap();
+        assert!(rbm.iter().zip(&other).all(|(a, b)| (a - b).abs() < 1e-13));
+        assert!(basis.project(&rbm[..6]).is_err() && basis.reconstruct(&c[..2]).is_err());
+    }
+
+    #[test]
+    fn dependent() {
+        // a repeated mode is discarded
+        let modes = [
+            Mode::Piston(SegmentId::CENTER),
+            Mode::Tip(SegmentId::CENTER),
+            Mode::Piston(SegmentId::CENTER),
+        ];
+        let basis = RbmBasis::new::<M1>(&modes);
+        assert_eq!(basis.labels(), &modes[..2]);
+        assert_eq!(basis.discarded(), &modes[2..]);
+        let rbm = Mode::Breathing.rbm::<M1>();
+        let breathing = Mirror::<M1>::rbm_basis(&[Modes::Breathing]);
+        let c = breathing.project(&rbm).unwrap();
+        assert!((c[0] - linalg::dot(&rbm, &rbm).sqrt()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,342 @@
+//! Monte Carlo misalignments
+//!
+//! The segment rigid body motions of a mirror are drawn from a [`Tolerance`] budget as
+//! independent segment rigid body motions followed by a global rigid body motion of the whole mirror.
+//! [`MonteCarlo::run`] gathers the [`Statistics`] of the edge gaps and steps and of the line of sight
+//! of the segment rotations.
+//!
+//! ```
+//! use geotrans::montecarlo::{Distribution, MonteCarlo, Tolerance};
+//! use geotrans::M1;
+//!
+//! let tolerance = Tolerance::new(Distribution::Gaussian, [1e-4, 1e-4, 5e-5, 1e-5, 1e-5, 1e-5])
+//!     .and_then(|tolerance| tolerance.global([0., 0., 0., 2e-6, 2e-6, 0.]))
+//!     .unwrap();
+//! let stats = MonteCarlo::<M1>::new(tolerance, 42).run(2).unwrap();
+//! println!("LOS x: {:e}rd", stats.rotation_line_of_sight[0].std);
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::{
+    linalg, rng::Rng, statistics::Summary, Error, Gmt, Mirror, Rbm, Segment, SegmentTrait,
+};
+
+/// Probability distribution of the tolerances
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// Uniform distribution in the range \[-√3σ,√3σ\]
+    Uniform,
+    /// Gaussian distribution
+    Gaussian,
+}
+
+/// Checks that the standard deviations are finite and positive or zero
+fn sigmas(sigma: [f64; 6]) -> Result<[f64; 6], Error> {
+    if sigma.iter().all(|s| s.is_finite() && *s >= 0f64) {
+        Ok(sigma)
+    } else {
+        Err(Error::Tolerance(
+            "the standard deviations must be finite and positive or zero",
+        ))
+    }
+}
+
+/// Tolerance budget of the rigid body motions of a mirror
+///
+/// The standard deviations σ are given for `[Tx,Ty,Tz,Rx,Ry,Rz]` in \[m\] and \[rd\]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tolerance {
+    distribution: Distribution,
+    segment: [f64; 6],
+    global: [f64; 6],
+    /// Row-major lower triangular Cholesky factor of the segment correlation matrix
+    cholesky: Option<Vec<f64>>,
+}
+impl Tolerance {
+    /// Creates a tolerance budget from the standard deviations of the segment rigid body motions
+    /// in the segment coordinates
+    ///
+    /// Returns an error if a standard deviation is negative or not finite
+    pub fn new(distribution: Distribution, segment: [f64; 6]) -> Result<Self, Error> {
+        Ok(Self {
+            distribution,
+            segment: sigmas(segment)?,
+            global: [0f64; 6],
+            cholesky: None,
+        })
+    }
+    /// Sets the standard deviations of the global rigid body motions of the mirror in the OSS
+    ///
+    /// The global rotations are about the center segment origin.
+    /// Returns an error if a standard deviation is negative or not finite
+    pub fn global(self, global: [f64; 6]) -> Result<Self, Error> {
+        Ok(Self {
+            global: sigmas(global)?,
+            ..self
+        })
+    }
+    /// Correlates the segment rigid body motions with the 6x6 `correlation` matrix
+    ///
+    /// The correlated deviates of a [`Distribution::Uniform`] budget are not uniformly distributed anymore
+    pub fn correlation(self, correlation: [[f64; 6]; 6]) -> Result<Self, Error> {
+        let c: Vec<f64> = correlation.iter().flatten().cloned().collect();
+        if (0..6).any(|i| c[i * 7] != 1f64) {
+            return Err(Error::Correlation);
+        }
+        let cholesky = linalg::cholesky(&c, 6).ok_or(Error::Correlation)?;
+        Ok(Self {
+            cholesky: Some(cholesky),
+            ..self
+        })
+    }
+    /// Returns a deviate with zero mean and unit variance
+    fn deviate(&self, rng: &mut Rng) -> f64 {
+        match self.distribution {
+            Distribution::Uniform => 3f64.sqrt() * (2f64 * rng.uniform() - 1f64),
+            Distribution::Gaussian => rng.normal(),
+        }
+    }
+    /// Draws the segment rigid body motions
+    fn segment(&self, rng: &mut Rng) -> Rbm {
+        let mut z = [0f64; 6];
+        z.iter_mut().for_each(|z| *z = self.deviate(rng));
+        if let Some(l) = &self.cholesky {
+            let y = z;
+            z.iter_mut()
+                .zip(l.chunks(6))
+                .for_each(|(z, l)| *z = linalg::dot(l, &y));
+        }
+        z.iter_mut().zip(&self.segment).for_each(|(z, s)| *z *= s);
+        Rbm::from(z)
+    }
+    /// Draws the global rigid body motions
+    fn global_rbm(&self, rng: &mut Rng) -> Rbm {
+        let mut g = [0f64; 6];
+        g.iter_mut()
+            .zip(&self.global)
+            .for_each(|(g, s)| *g = s * self.deviate(rng));
+        Rbm::from(g)
+    }
+}
+
+/// Monte Carlo generator of the misalignments of the mirror `M`
+///
+/// The generator is an iterator over the 7 segment rigid body motions of random misalignments
+#[derive(Debug, Clone)]
+pub struct MonteCarlo<M: Gmt> {
+    tolerance: Tolerance,
+    rng: Rng,
+    mirror: PhantomData<M>,
+}
+impl<M: Gmt> MonteCarlo<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Creates a new generator for the `tolerance` budget
+    ///
+    /// The same `seed` reproduces the same misalignments
+    pub fn new(tolerance: Tolerance, seed: u64) -> Self {
+        Self {
+            tolerance,
+            rng: Rng::seed_from_u64(seed),
+            mirror: PhantomData,
+        }
+    }
+    /// Draws the 7 segment rigid body motions, in the segment coordinates, of a random misalignment
+    pub fn draw(&mut self) -> Vec<Rbm> {
+        let segments: Vec<Rbm> = (0..7)
+            .map(|_| self.tolerance.segment(&mut self.rng))
+            .collect();
+        let global = self.tolerance.global_rbm(&mut self.rng);
+        segments
+            .iter()
+            .zip(Mirror::<M>::global_2_rigidbodymotions(&global))
+            .map(|(segment, global)| segment.compose(&global))
+            .collect()
+    }
+    /// Draws `n` random misalignments and returns the statistics of the edges and of the line of sight
+    pub fn run(&mut self, n: usize) -> Result<Statistics, Error> {
+        let mut gaps = vec![vec![]; 12];
+        let mut steps = vec![vec![]; 12];
+        let mut min_gap = vec![];
+        let mut los = [vec![], vec![]];
+        for rbms in self.by_ref().take(n) {
+            let rbm = Rbm::to_mirror(&rbms);
+            let edges = Mirror::<M>::edges(&rbm)?;
+            edges.iter().enumerate().for_each(|(i, edge)| {
+                gaps[i].push(edge.gap);
+                steps[i].push(edge.step);
+            });
+            min_gap.push(
+                edges
+                    .iter()
+                    .map(|edge| edge.gap)
+                    .fold(f64::INFINITY, f64::min),
+            );
+            let [x, y] = Mirror::<M>::rotation_line_of_sight(&rbm)?;
+            los[0].push(x);
+            los[1].push(y);
+        }
+        Ok(Statistics {
+            n_sample: n,
+            gaps: gaps.iter().map(|x| Summary::new(x)).collect(),
+            steps: steps.iter().map(|x| Summary::new(x)).collect(),
+            min_gap: Summary::new(&min_gap),
+            rotation_line_of_sight: [Summary::new(&los[0]), Summary::new(&los[1])],
+        })
+    }
+}
+impl<M: Gmt> Iterator for MonteCarlo<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    type Item = Vec<Rbm>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.draw())
+    }
+}
+
+/// Statistics of a Monte Carlo run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    /// Number of misalignments
+    pub n_sample: usize,
+    /// Gaps of the 12 edges, in the order of [`Mirror::neighbours`] \[m\]
+    pub gaps: Vec<Summary>,
+    /// Steps of the 12 edges, in the order of [`Mirror::neighbours`] \[m\]
+    pub steps: Vec<Summary>,
+    /// Smallest gap of each misalignment \[m\]
+    pub min_gap: Summary,
+    /// Line of sight of the segment rotations about the OSS x and y axes \[rd\]
+    pub rotation_line_of_sight: [Summary; 2],
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the line of sight error about the OSS x and y axes of the segment rotations
+    /// of the mirror rigid body motions `rbm` \[rd\]
+    ///
+    /// The line of sight is the geometric approximation of twice, for the reflection, the mean tilt
+    /// of the segments in the OSS, weighted by the segment aperture areas.
+    /// The segment translations are ignored: their contribution depends on the optical design.
+    /// `rbm` is the 42 elements vector of the 7 segment rigid body motions in the segment coordinates
+    pub fn rotation_line_of_sight(rbm: &[f64]) -> Result<[f64; 2], Error> {
+        let (mut los, mut area) = ([0f64; 2], 0f64);
+        for (segment, rbm) in Self::segments().zip(Rbm::from_mirror(rbm)?) {
+            let q = rbm.quaternion();
+            let (rx, ry, _) = segment
+                .rotation()
+                .map_or(q.clone(), |q_s| &q_s * q * q_s.complex_conjugate())
+                .euler_angles();
+            let w = segment.aperture().area();
+            los[0] += w * rx;
+            los[1] += w * ry;
+            area += w;
+        }
+        Ok(los.map(|x| 2f64 * x / area))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[test]
+    fn reproducible() {
+        let tolerance = Tolerance::new(Distribution::Uniform, [1e-4; 6])
+            .and_then(|tolerance| tolerance.global([1e-5; 6]))
+            .unwrap();
+        let a: Vec<_> = MonteCarlo::<M2>::new(tolerance.clone(), 3)
+            .take(5)
+            .collect();
+        let b: Vec<_> = MonteCarlo::<M2>::new(tolerance.clone(), 3)
+            .take(5)
+            .collect();
+        assert_eq!(a, b);
+        assert_ne!(a[0], MonteCarlo::<M2>::new(tolerance, 4).draw());
+    }
+
+    #[test]
+    fn segment_sigma() {
+        let sigma = [1e-4, 2e-4, 3e-5, 1e-5, 2e-5, 3e-5];
+        let tolerance = Tolerance::new(Distribution::Gaussian, sigma).unwrap();
+        let rbms: Vec<Rbm> = MonteCarlo::<M1>::new(tolerance, 1)
+            .take(1000)
+            .flatten()
+            .collect();
+        for (i, s) in sigma.iter().enumerate() {
+            let x: Vec<f64> = rbms.iter().map(|rbm| <[f64; 6]>::from(*rbm)[i]).collect();
+            let summary = Summary::new(&x);
+            assert!((summary.std / s - 1.).abs() < 0.05);
+            assert!(summary.mean.abs() < 0.1 * s);
+        }
+    }
+
+    #[test]
+    fn correlated() {
+        let mut c = [[0f64; 6]; 6];
+        (0..6).for_each(|i| c[i][i] = 1.);
+        c[0][4] = 0.9;
+        c[4][0] = 0.9;
+        let tolerance = Tolerance::new(Distribution::Gaussian, [1e-3; 6])
+            .unwrap()
+            .correlation(c)
+            .unwrap();
+        let rbms: Vec<Rbm> = MonteCarlo::<M2>::new(tolerance, 5)
+            .take(1000)
+            .flatten()
+            .collect();
+        let r = rbms
+            .iter()
+            .map(|rbm| rbm.translation[0] * rbm.rotation[1])
+            .sum::<f64>()
+            / rbms.len() as f64
+            / 1e-6;
+        assert!((r - 0.9).abs() < 0.1, "{}", r);
+        c[0][4] = 1.1;
+        c[4][0] = 1.1;
+        assert!(matches!(
+            Tolerance::new(Distribution::Gaussian, [1.; 6])
+                .unwrap()
+                .correlation(c),
+            Err(Error::Correlation)
+        ));
+    }
+
+    #[test]
+    fn global_line_of_sight() {
+        let global = Rbm::new([0.; 3], [1e-6, -2e-6, 0.]);
+        let rbm = Rbm::to_mirror(&Mirror::<M1>::global_2_rigidbodymotions(&global));
+        let [x, y] = Mirror::<M1>::rotation_line_of_sight(&rbm).unwrap();
+        assert!((x - 2e-6).abs() < 1e-12 && (y + 4e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn statistics() {
+        let tolerance =
+            Tolerance::new(Distribution::Gaussian, [1e-4, 1e-4, 0., 0., 0., 0.]).unwrap();
+        let stats = MonteCarlo::<M1>::new(tolerance, 7).run(3).unwrap();
+        assert_eq!(stats.gaps.len(), 12);
+        assert!(stats.min_gap.min <= stats.gaps[0].min);
+        // the segment translations are ignored
+        assert!(stats.rotation_line_of_sight[0].max.abs() < 1e-12);
+    }
+
+    #[test]
+    fn invalid_sigma() {
+        for sigma in [-1e-6, f64::NAN, f64::INFINITY] {
+            let mut s = [1e-6; 6];
+            s[3] = sigma;
+            assert!(matches!(
+                Tolerance::new(Distribution::Gaussian, s),
+                Err(Error::Tolerance(_))
+            ));
+            let tolerance = Tolerance::new(Distribution::Uniform, [0.; 6]).unwrap();
+            assert!(matches!(tolerance.global(s), Err(Error::Tolerance(_))));
+        }
+    }
+}
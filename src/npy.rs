@@ -0,0 +1,498 @@
+//! NumPy `.npy` and `.npz` files
+//!
+//! Arrays are written as little-endian `f64` (`<f8`) in C order, and can be loaded with `numpy.load`.
+//! `.npz` archives are written without compression, as `numpy.savez` does,
+//! and only uncompressed archives can be read back.
+
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{Error, Gmt, InteractionMatrix, Mirror, Segment, SegmentTrait, Vector};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+/// Largest header length accepted on read \[byte\]
+const MAX_HEADER_LEN: usize = 1 << 16;
+
+/// N-dimensional array of `f64`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Array {
+    shape: Vec<usize>,
+    data: Vec<f64>,
+}
+impl Array {
+    /// Creates a new array with the given `shape` from the `data` in C order
+    pub fn new(shape: Vec<usize>, data: Vec<f64>) -> Result<Self, Error> {
+        let n = shape.iter().try_fold(1usize, |n, &d| n.checked_mul(d));
+        if n != Some(data.len()) {
+            return Err(Error::Npy(format!(
+                "shape {:?} does not match the data length {}",
+                shape,
+                data.len()
+            )));
+        }
+        Ok(Self { shape, data })
+    }
+    /// Returns the array shape
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+    /// Returns the array data in C order
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+    /// Returns the array data in C order
+    pub fn into_data(self) -> Vec<f64> {
+        self.data
+    }
+    /// Returns the array with a new shape
+    pub fn reshape(self, shape: Vec<usize>) -> Result<Self, Error> {
+        Self::new(shape, self.data)
+    }
+    /// Returns the `.npy` header
+    fn header(&self) -> Vec<u8> {
+        let shape = match self.shape.as_slice() {
+            [n] => format!("({},)", n),
+            shape => format!(
+                "({})",
+                shape
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': {}, }}",
+            shape
+        );
+        // magic + version + header length + header + '\n' is a multiple of 64 bytes
+        let n = MAGIC.len() + 4 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - n % 64) % 64));
+        header.push('\n');
+        header.into_bytes()
+    }
+    /// Writes the array in the `.npy` format
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let header = self.header();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(&header)?;
+        for x in &self.data {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+        Ok(())
+    }
+    /// Reads an array in the `.npy` format
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic[..6] != MAGIC {
+            return Err(Error::Npy("not a NumPy file".to_string()));
+        }
+        let header_len = match magic[6] {
+            1 => {
+                let mut n = [0u8; 2];
+                reader.read_exact(&mut n)?;
+                u16::from_le_bytes(n) as usize
+            }
+            2 | 3 => {
+                let mut n = [0u8; 4];
+                reader.read_exact(&mut n)?;
+                u32::from_le_bytes(n) as usize
+            }
+            v => return Err(Error::Npy(format!("unsupported version {}", v))),
+        };
+        if header_len > MAX_HEADER_LEN {
+            return Err(Error::Npy(format!(
+                "header length {} is too large",
+                header_len
+            )));
+        }
+        let mut header = vec![0u8; header_len];
+        reader.read_exact(&mut header)?;
+        let header = String::from_utf8_lossy(&header);
+        let value = |key: &str| -> Result<&str, Error> {
+            let pattern = format!("'{}':", key);
+            header
+                .find(&pattern)
+                .map(|i| header[i + pattern.len()..].trim_start())
+                .ok_or_else(|| Error::Npy(format!("missing {} in header", key)))
+        };
+        if !value("descr")?.starts_with("'<f8'") {
+            return Err(Error::Npy(format!("unsupported data type in {}", header)));
+        }
+        let fortran_order = value("fortran_order")?.starts_with("True");
+        let shape_str = value("shape")?;
+        let shape = shape_str
+            .strip_prefix('(')
+            .and_then(|s| s.split_once(')'))
+            .ok_or_else(|| Error::Npy(format!("invalid shape: {}", shape_str)))?
+            .0
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<usize>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Npy(format!("invalid shape: {}", e)))?;
+        let n_bytes = shape
+            .iter()
+            .try_fold(8usize, |n, &d| n.checked_mul(d))
+            .ok_or_else(|| Error::Npy(format!("array too large: {:?}", shape)))?;
+        let n = n_bytes / 8;
+        // the buffer grows with the data actually read, not with the header shape
+        let mut bytes = vec![];
+        reader.take(n_bytes as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != n_bytes {
+            return Err(Error::Npy(format!(
+                "expected {} values, found {}",
+                n,
+                bytes.len() / 8
+            )));
+        }
+        let data: Vec<f64> = bytes
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(<[u8; 8]>::try_from(b).unwrap()))
+            .collect();
+        let data = if fortran_order && shape.len() > 1 {
+            // column major to row major
+            let mut c = vec![0f64; n];
+            for (f, x) in data.into_iter().enumerate() {
+                let mut rem = f;
+                let idx: Vec<usize> = shape
+                    .iter()
+                    .map(|&d| {
+                        let i = rem % d;
+                        rem /= d;
+                        i
+                    })
+                    .collect();
+                c[idx.iter().zip(&shape).fold(0, |a, (i, d)| a * d + i)] = x;
+            }
+            c
+        } else {
+            data
+        };
+        Self::new(shape, data)
+    }
+}
+impl From<Vec<f64>> for Array {
+    fn from(data: Vec<f64>) -> Self {
+        Self {
+            shape: vec![data.len()],
+            data,
+        }
+    }
+}
+impl From<&[f64]> for Array {
+    fn from(data: &[f64]) -> Self {
+        data.to_vec().into()
+    }
+}
+impl From<&[[f64; 3]]> for Array {
+    fn from(points: &[[f64; 3]]) -> Self {
+        Self {
+            shape: vec![points.len(), 3],
+            data: points.iter().flatten().cloned().collect(),
+        }
+    }
+}
+impl From<&[Vector]> for Array {
+    fn from(points: &[Vector]) -> Self {
+        Self {
+            shape: vec![points.len(), 3],
+            data: points.iter().flat_map(|v| v.iter().cloned()).collect(),
+        }
+    }
+}
+impl From<&InteractionMatrix> for Array {
+    fn from(d: &InteractionMatrix) -> Self {
+        Self {
+            shape: vec![d.n_sensor(), d.n_rbm()],
+            data: d.rows().flatten().cloned().collect(),
+        }
+    }
+}
+impl TryFrom<&[Vec<f64>]> for Array {
+    type Error = Error;
+    /// Creates a 2D array from the matrix rows
+    fn try_from(rows: &[Vec<f64>]) -> Result<Self, Self::Error> {
+        let n = rows.first().map_or(0, |r| r.len());
+        Self::new(
+            vec![rows.len(), n],
+            rows.iter()
+                .map(|r| {
+                    if r.len() == n {
+                        Ok(r.as_slice())
+                    } else {
+                        Err(Error::Npy("rows of different lengths".to_string()))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .concat(),
+        )
+    }
+}
+
+/// Returns the 7x3 array of the segment origins in the OSS
+pub fn segment_translations<M: Gmt>() -> Array
+where
+    Segment<M>: SegmentTrait,
+{
+    let data = Mirror::<M>::segments()
+        .flat_map(|segment| segment.translation())
+        .collect();
+    Array {
+        shape: vec![7, 3],
+        data,
+    }
+}
+/// Returns the 7x3x3 array of the segment rotation matrices from the segment coordinates to the OSS
+pub fn segment_rotations<M: Gmt>() -> Array
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    let data = Mirror::<M>::segments()
+        .flat_map(|segment| segment.rotation_matrix())
+        .flatten()
+        .collect();
+    Array {
+        shape: vec![7, 3, 3],
+        data,
+    }
+}
+
+/// Saves an array to a `.npy` file
+pub fn save<P: AsRef<Path>>(path: P, array: &Array) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    array.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+/// Loads an array from a `.npy` file
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Array, Error> {
+    Array::read(BufReader::new(File::open(path)?))
+}
+
+/// CRC-32 checksum (IEEE polynomial)
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |c, _| {
+            if c & 1 == 1 {
+                (c >> 1) ^ 0xedb88320
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+/// Writes the named arrays into an uncompressed `.npz` archive
+///
+/// The `.npy` extension is appended to the names
+pub fn write_npz<W: Write>(mut writer: W, arrays: &[(&str, &Array)]) -> Result<(), Error> {
+    let mut offset = 0usize;
+    let mut central = vec![];
+    for (name, array) in arrays {
+        let name = format!("{}.npy", name);
+        let mut data = vec![];
+        array.write(&mut data)?;
+        let (size, name_len, local_offset) = (
+            u32::try_from(data.len()).map_err(|_| Error::Npy("array too large".to_string()))?,
+            name.len() as u16,
+            u32::try_from(offset).map_err(|_| Error::Npy("archive too large".to_string()))?,
+        );
+        let crc = crc32(&data);
+        // version, flags, method, time, date, crc, sizes, name length
+        let mut common = vec![];
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0x21u16.to_le_bytes());
+        common.extend(crc.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend(name_len.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        let mut local = vec![];
+        local.extend(0x04034b50u32.to_le_bytes());
+        local.extend(&common);
+        local.extend(name.as_bytes());
+        writer.write_all(&local)?;
+        writer.write_all(&data)?;
+        offset += local.len() + data.len();
+        central.extend(0x02014b50u32.to_le_bytes());
+        central.extend(20u16.to_le_bytes());
+        central.extend(&common);
+        // comment length, disk, internal and external attributes
+        central.extend([0u8; 10]);
+        central.extend(local_offset.to_le_bytes());
+        central.extend(name.as_bytes());
+    }
+    writer.write_all(&central)?;
+    let n = arrays.len() as u16;
+    let mut end = vec![];
+    end.extend(0x06054b50u32.to_le_bytes());
+    end.extend([0u8; 4]);
+    end.extend(n.to_le_bytes());
+    end.extend(n.to_le_bytes());
+    end.extend((central.len() as u32).to_le_bytes());
+    end.extend((offset as u32).to_le_bytes());
+    end.extend(0u16.to_le_bytes());
+    writer.write_all(&end)?;
+    Ok(())
+}
+/// Reads the named arrays of an uncompressed `.npz` archive
+pub fn read_npz<R: Read>(mut reader: R) -> Result<Vec<(String, Array)>, Error> {
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes)?;
+    let u16_at = |i: usize| -> Result<usize, Error> {
+        bytes
+            .get(i..i + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| Error::Npy("truncated archive".to_string()))
+    };
+    let u32_at = |i: usize| -> Result<usize, Error> {
+        bytes
+            .get(i..i + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| Error::Npy("truncated archive".to_string()))
+    };
+    let end = (0..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|&i| bytes[i..i + 4] == 0x06054b50u32.to_le_bytes())
+        .ok_or_else(|| Error::Npy("not a zip archive".to_string()))?;
+    let n = u16_at(end + 10)?;
+    let mut cd = u32_at(end + 16)?;
+    let mut arrays = vec![];
+    for _ in 0..n {
+        if u32_at(cd)? != 0x02014b50 {
+            return Err(Error::Npy("invalid central directory".to_string()));
+        }
+        let method = u16_at(cd + 10)?;
+        let size = u32_at(cd + 20)?;
+        let (name_len, extra_len, comment_len) =
+            (u16_at(cd + 28)?, u16_at(cd + 30)?, u16_at(cd + 32)?);
+        let local = u32_at(cd + 42)?;
+        let name = String::from_utf8_lossy(
+            bytes
+                .get(cd + 46..cd + 46 + name_len)
+                .ok_or_else(|| Error::Npy("truncated archive".to_string()))?,
+        )
+        .to_string();
+        if method != 0 {
+            return Err(Error::Npy(format!("{} is compressed", name)));
+        }
+        let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+        let data = bytes
+            .get(start..start + size)
+            .ok_or_else(|| Error::Npy("truncated archive".to_string()))?;
+        arrays.push((
+            name.trim_end_matches(".npy").to_string(),
+            Array::read(data)?,
+        ));
+        cd += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(arrays)
+}
+/// Saves the named arrays to an uncompressed `.npz` file
+pub fn savez<P: AsRef<Path>>(path: P, arrays: &[(&str, &Array)]) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_npz(&mut writer, arrays)?;
+    writer.flush()?;
+    Ok(())
+}
+/// Loads the named arrays from an uncompressed `.npz` file
+pub fn loadz<P: AsRef<Path>>(path: P) -> Result<Vec<(String, Array)>, Error> {
+    read_npz(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mirror, M1, M2};
+
+    #[test]
+    fn npy_roundtrip() {
+        let a = segment_rotations::<M2>();
+        let mut buffer = vec![];
+        a.write(&mut buffer).unwrap();
+        assert_eq!(&buffer[..6], MAGIC);
+        let header_len = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(buffer.len(), 10 + header_len + 63 * 8);
+        assert_eq!(Array::read(buffer.as_slice()).unwrap(), a);
+    }
+
+    #[test]
+    fn npy_fortran_order() {
+        let mut buffer = vec![];
+        Array::new(vec![2, 3], vec![1., 4., 2., 5., 3., 6.])
+            .unwrap()
+            .write(&mut buffer)
+            .unwrap();
+        let i = buffer.windows(5).position(|w| w == b"False").unwrap();
+        buffer[i..i + 5].copy_from_slice(b"True ");
+        let a = Array::read(buffer.as_slice()).unwrap();
+        assert_eq!(a.data(), &[1., 2., 3., 4., 5., 6.]);
+    }
+
+    #[test]
+    fn npy_invalid_shape() {
+        let mut buffer = vec![];
+        Array::from(vec![1., 2.]).write(&mut buffer).unwrap();
+        let header = |buffer: &mut Vec<u8>, shape: &[u8]| {
+            let i = buffer.windows(5).position(|w| w == b"(2,),").unwrap();
+            buffer[i..i + 5].copy_from_slice(shape);
+        };
+        for shape in [b"2,), ", b"(2,  ", b"(x,),"] {
+            let mut buffer = buffer.clone();
+            header(&mut buffer, shape);
+            assert!(matches!(Array::read(buffer.as_slice()), Err(Error::Npy(_))));
+        }
+        // the array is larger than the data
+        let mut buffer = buffer.clone();
+        header(&mut buffer, b"(3,),");
+        assert!(matches!(Array::read(buffer.as_slice()), Err(Error::Npy(_))));
+        // the number of bytes overflows
+        let mut buffer = vec![];
+        Array::from(vec![1.]).write(&mut buffer).unwrap();
+        let i = buffer.windows(5).position(|w| w == b"(1,),").unwrap();
+        let shape = format!("({},{}),", usize::MAX / 2, 3);
+        buffer.splice(i..i + 5, shape.bytes());
+        let header_len = u16::from_le_bytes([buffer[8], buffer[9]]) + shape.len() as u16 - 5;
+        buffer[8..10].copy_from_slice(&header_len.to_le_bytes());
+        assert!(matches!(Array::read(buffer.as_slice()), Err(Error::Npy(_))));
+        assert!(Array::new(vec![usize::MAX, 2], vec![]).is_err());
+        // the header length is larger than the file
+        let mut buffer = MAGIC.to_vec();
+        buffer.extend([2, 0]);
+        buffer.extend(u32::MAX.to_le_bytes());
+        assert!(matches!(Array::read(buffer.as_slice()), Err(Error::Npy(_))));
+    }
+
+    #[test]
+    fn npz_roundtrip() {
+        let t = segment_translations::<M1>();
+        let rbm = Array::from(Mirror::<M1>::tiptilt_2_rigidbodymotions((1e-6, 0.)))
+            .reshape(vec![7, 6])
+            .unwrap();
+        let mut buffer = vec![];
+        write_npz(&mut buffer, &[("translations", &t), ("rbm", &rbm)]).unwrap();
+        let arrays = read_npz(buffer.as_slice()).unwrap();
+        assert_eq!(arrays[0].0, "translations");
+        assert_eq!(arrays[0].1, t);
+        assert_eq!(arrays[1].0, "rbm");
+        assert_eq!(arrays[1].1.shape(), &[7, 6]);
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+}
@@ -0,0 +1,253 @@
+//! Unsensed (null-space) modes of a sensor interaction matrix
+//!
+//! The interaction matrix maps the segment rigid body motions (6 per segment,
+//! 42 per mirror) to the sensor measurements.
+//! Several sensor sets (e.g. edge sensors and global metrology) can be stacked together
+//! before computing the modes that none of the sensors can see.
+
+use crate::{linalg, Error};
+
+/// Number of rigid body motions of a GMT mirror
+pub const N_RBM: usize = 42;
+
+/// Sensor interaction matrix
+#[derive(Debug, Clone)]
+pub struct InteractionMatrix {
+    /// Number of columns (rigid body motions)
+    n_rbm: usize,
+    /// Row-major matrix, one row per sensor
+    data: Vec<f64>,
+}
+impl Default for InteractionMatrix {
+    fn default() -> Self {
+        Self {
+            n_rbm: N_RBM,
+            data: vec![],
+        }
+    }
+}
+impl InteractionMatrix {
+    /// Creates an empty interaction matrix for `n_rbm` rigid body motions
+    ///
+    /// Returns an error if `n_rbm` is 0
+    pub fn new(n_rbm: usize) -> Result<Self, Error> {
+        if n_rbm == 0 {
+            return Err(Error::EmptyInteraction);
+        }
+        Ok(Self {
+            n_rbm,
+            data: vec![],
+        })
+    }
+    /// Creates an interaction matrix from the sensor rows
+    pub fn from_rows<R: AsRef<[f64]>>(n_rbm: usize, rows: &[R]) -> Result<Self, Error> {
+        let mut d = Self::new(n_rbm)?;
+        for row in rows {
+            d.push(row)?;
+        }
+        Ok(d)
+    }
+    /// Adds a sensor row
+    pub fn push<R: AsRef<[f64]>>(&mut self, row: R) -> Result<&mut Self, Error> {
+        let row = row.as_ref();
+        if row.len() != self.n_rbm {
+            return Err(Error::InteractionRow(row.len(), self.n_rbm));
+        }
+        self.data.extend_from_slice(row);
+        Ok(self)
+    }
+    /// Stacks the sensors of another interaction matrix below the sensors of this one
+    pub fn stack(mut self, other: &InteractionMatrix) -> Result<Self, Error> {
+        if other.n_rbm != self.n_rbm {
+            return Err(Error::InteractionRow(other.n_rbm, self.n_rbm));
+        }
+        self.data.extend_from_slice(&other.data);
+        Ok(self)
+    }
+    /// Returns the number of sensors
+    pub fn n_sensor(&self) -> usize {
+        self.data.len() / self.n_rbm
+    }
+    /// Returns the number of rigid body motions
+    pub fn n_rbm(&self) -> usize {
+        self.n_rbm
+    }
+    /// Returns the sensor rows
+    pub fn rows(&self) -> std::slice::Chunks<'_, f64> {
+        self.data.chunks(self.n_rbm)
+    }
+    /// Returns the sensor measurements for the rigid body motions `rbm`
+    pub fn measure(&self, rbm: &[f64]) -> Result<Vec<f64>, Error> {
+        if rbm.len() != self.n_rbm {
+            return Err(Error::InteractionRow(rbm.len(), self.n_rbm));
+        }
+        Ok(self.rows().map(|row| linalg::dot(row, rbm)).collect())
+    }
+    /// Returns the unsensed modes
+    ///
+    /// A mode is unsensed if its singular value is less than `rcond` times the largest singular value
+    pub fn null_space(&self, rcond: f64) -> NullSpace {
+        let n = self.n_rbm;
+        let (singular_values, singular_vectors) = linalg::svd(&self.data, self.n_sensor(), n);
+        let threshold = rcond * singular_values.first().cloned().unwrap_or_default();
+        let (sensed, unsensed): (Vec<_>, Vec<_>) = singular_values
+            .into_iter()
+            .zip(singular_vectors)
+            .partition(|(s, _)| *s > threshold);
+        NullSpace {
+            singular_values: sensed.into_iter().map(|(s, _)| s).collect(),
+            modes: unsensed.into_iter().map(|(_, v)| v).collect(),
+        }
+    }
+}
+
+/// Unsensed modes of an [`InteractionMatrix`]
+#[derive(Debug, Clone)]
+pub struct NullSpace {
+    /// Non-zero singular values of the interaction matrix
+    singular_values: Vec<f64>,
+    /// Orthonormal unsensed modes
+    modes: Vec<Vec<f64>>,
+}
+impl NullSpace {
+    /// Returns the orthonormal unsensed modes
+    pub fn modes(&self) -> &[Vec<f64>] {
+        &self.modes
+    }
+    /// Returns the number of unsensed modes
+    pub fn len(&self) -> usize {
+        self.modes.len()
+    }
+    /// Checks if all the modes are sensed
+    pub fn is_empty(&self) -> bool {
+        self.modes.is_empty()
+    }
+    /// Returns the singular values of the sensed modes
+    pub fn singular_values(&self) -> &[f64] {
+        &self.singular_values
+    }
+    /// Returns the coefficients of the rigid body motions `rbm` on the unsensed modes
+    pub fn project(&self, rbm: &[f64]) -> Vec<f64> {
+        self.modes.iter().map(|m| linalg::dot(m, rbm)).collect()
+    }
+    /// Returns the fraction, in the range \[0,1\], of the rigid body motions `rbm` norm
+    /// that lies in the unsensed modes
+    pub fn unsensed_fraction(&self, rbm: &[f64]) -> f64 {
+        let norm = linalg::dot(rbm, rbm).sqrt();
+        if norm == 0f64 {
+            return 0f64;
+        }
+        self.project(rbm).iter().map(|c| c * c).sum::<f64>().sqrt() / norm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Conic, Focus, Mirror, Rbm, Segment, SegmentTrait, Transform, Vector, M1};
+
+    // relative segment pistons between neighbouring segments
+    fn piston_sensors() -> InteractionMatrix {
+        let mut d = InteractionMatrix::default();
+        for (a, b) in (0..6)
+            .map(|i| (i, (i + 1) % 6))
+            .chain((0..6).map(|i| (i, 6)))
+        {
+            let mut row = vec![0f64; N_RBM];
+            row[a * 6 + 2] = 1f64;
+            row[b * 6 + 2] = -1f64;
+            d.push(row).unwrap();
+        }
+        d
+    }
+
+    #[test]
+    fn relative_piston() {
+        let d = piston_sensors();
+        let null = d.null_space(1e-9);
+        assert_eq!(null.len(), 36);
+        let mut piston = vec![0f64; N_RBM];
+        (0..7).for_each(|i| piston[i * 6 + 2] = 1f64);
+        assert!((null.unsensed_fraction(&piston) - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stacked_metrology() {
+        let mut global = InteractionMatrix::default();
+        let mut row = vec![0f64; N_RBM];
+        row[6 * 6 + 2] = 1f64;
+        global.push(row).unwrap();
+        let d = piston_sensors().stack(&global).unwrap();
+        assert_eq!(d.n_sensor(), 13);
+        assert_eq!(d.null_space(1e-9).len(), 35);
+    }
+
+    // edge sensors measuring the height difference between neighbouring segments
+    // at 2 points of each edge
+    fn edge_sensors() -> InteractionMatrix {
+        let segments: Vec<Segment<M1>> = (1..=7).map(|sid| Segment::new(sid).unwrap()).collect();
+        let conic = Conic::m1();
+        let mut d = InteractionMatrix::default();
+        for (a, b) in (0..6)
+            .map(|i| (i, (i + 1) % 6))
+            .chain((0..6).map(|i| (i, 6)))
+        {
+            let (ca, cb) = (segments[a].translation(), segments[b].translation());
+            let u = Vector::from([cb[0] - ca[0], cb[1] - ca[1], 0.]);
+            let e = Vector::k().cross(&u) / u.norm();
+            let middle = Vector::from([0.5 * (ca[0] + cb[0]), 0.5 * (ca[1] + cb[1]), 0.]);
+            for o in [-0.5, 0.5] {
+                let p = middle.clone() + &e * o;
+                let z = conic.height(p[0].hypot(p[1]));
+                let mut row = vec![0f64; N_RBM];
+                for (i, sign) in [(a, -1f64), (b, 1f64)] {
+                    let segment = &segments[i];
+                    let v = Vector::from([p[0], p[1], z]).fro(segment.clone());
+                    // first order height change for the unit segment rigid body motions
+                    for k in 0..6 {
+                        let mut w = [0f64; 3];
+                        w[k % 3] = 1f64;
+                        let dv = if k < 3 {
+                            Vector::from(w)
+                        } else {
+                            Vector::from(w).cross(&v)
+                        };
+                        row[i * 6 + k] = sign * dv.vtov(segment.clone())[2];
+                    }
+                }
+                d.push(row).unwrap();
+            }
+        }
+        d
+    }
+
+    #[test]
+    fn edge_sensors_unsensed() {
+        let d = edge_sensors();
+        assert_eq!(d.n_sensor(), 24);
+        let null = d.null_space(1e-6);
+        // the whole mirror tip-tilt
+        let tiptilt = Mirror::<M1>::tiptilt_2_rigidbodymotions((1e-6, -2e-6));
+        assert!(null.unsensed_fraction(&tiptilt) > 1. - 1e-9);
+        // the whole mirror clocking
+        let clocking: Vec<f64> = Mirror::<M1>::global_jacobian()
+            .chunks(6)
+            .map(|row| row[5])
+            .collect();
+        assert!(null.unsensed_fraction(&clocking) > 1. - 1e-9);
+        // the radial scaling of the segments on the parent conic, to first order
+        let focus = Rbm::to_mirror(&Mirror::<M1>::focus_rigidbodymotions(&Focus::new(1e-5)));
+        assert!(null.unsensed_fraction(&focus) > 0.999);
+        // a single segment piston is sensed
+        let mut piston = vec![0f64; N_RBM];
+        piston[2] = 1f64;
+        assert!(null.unsensed_fraction(&piston) < 0.9);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(InteractionMatrix::new(0).is_err());
+        assert!(InteractionMatrix::from_rows(0, &[[0f64; 0]]).is_err());
+        assert!(InteractionMatrix::from_rows(N_RBM, &[[0f64; 6]]).is_err());
+    }
+}
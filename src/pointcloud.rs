@@ -0,0 +1,530 @@
+//! Point cloud readers and writers
+//!
+//! Point clouds are stored as comma, semicolon or whitespace separated tables, one point per line,
+//! the delimiter is detected from the first line that is not a comment.
+//! Comment lines starting with `#` may set the coordinate frame and the length unit:
+//! ```text
+//! # frame: M1S3
+//! # units: mm
+//! id,x,y,z
+//! 1,10.5,-3.2,0.1
+//! ```
+//! The `x`, `y` and `z` columns are found from the optional header line, where the column names
+//! may carry a unit suffix (`x[mm]`, `x (mm)` or `x_mm`); otherwise the first 3 columns after
+//! the leading text columns (e.g. point ids) of the first point are used.
+//! Empty fields are kept, and an empty `x`, `y` or `z` cell is an error.
+//! A `unit` header column gives the unit of each point.
+//! All the coordinates are converted to meters on load.
+
+use std::{
+    convert::TryFrom,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{Error, Segment, SegmentId, SegmentTrait, Transform, Vector, M1, M2};
+
+/// Point coordinates frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Frame {
+    /// Observatory coordinate system
+    #[default]
+    Oss,
+    /// [`M1`] segment coordinate system
+    M1(SegmentId),
+    /// [`M2`] segment coordinate system
+    M2(SegmentId),
+}
+impl FromStr for Frame {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let frame = s.trim().to_uppercase();
+        // the segment frames are exactly `M1S<id>` or `M2S<id>`
+        let sid = |m: &str| -> Result<i32, Error> {
+            frame
+                .strip_prefix(m)
+                .filter(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|id| id.parse::<i32>().ok())
+                .ok_or_else(|| Error::Frame(s.to_string()))
+        };
+        match frame.as_str() {
+            "OSS" => Ok(Frame::Oss),
+            f if f.starts_with("M1") => Ok(Frame::M1(SegmentId::try_from(sid("M1S")?)?)),
+            f if f.starts_with("M2") => Ok(Frame::M2(SegmentId::try_from(sid("M2S")?)?)),
+            _ => Err(Error::Frame(s.to_string())),
+        }
+    }
+}
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Frame::Oss => write!(f, "OSS"),
+            Frame::M1(sid) => write!(f, "M1S{}", sid),
+            Frame::M2(sid) => write!(f, "M2S{}", sid),
+        }
+    }
+}
+
+/// Length units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Unit {
+    /// meter
+    #[default]
+    M,
+    /// millimeter
+    Mm,
+}
+impl Unit {
+    /// Returns the unit in meters
+    pub fn meters(&self) -> f64 {
+        match self {
+            Unit::M => 1f64,
+            Unit::Mm => 1e-3,
+        }
+    }
+}
+impl FromStr for Unit {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "m" => Ok(Unit::M),
+            "mm" => Ok(Unit::Mm),
+            _ => Err(Error::Unit(s.to_string())),
+        }
+    }
+}
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::M => write!(f, "m"),
+            Unit::Mm => write!(f, "mm"),
+        }
+    }
+}
+
+/// Column delimiters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Delimiter {
+    #[default]
+    Comma,
+    Semicolon,
+    Whitespace,
+}
+impl Delimiter {
+    /// Detects the delimiter of a line: comma, then semicolon, otherwise whitespace
+    fn detect(line: &str) -> Self {
+        if line.contains(',') {
+            Delimiter::Comma
+        } else if line.contains(';') {
+            Delimiter::Semicolon
+        } else {
+            Delimiter::Whitespace
+        }
+    }
+}
+
+/// Splits a line into its tokens
+///
+/// The empty fields between comma or semicolon delimiters are kept.
+/// For whitespace delimited lines, a unit in brackets or parentheses is joined to the previous
+/// token, e.g. `x (mm)`
+fn tokenize(line: &str, delimiter: Delimiter) -> Vec<String> {
+    match delimiter {
+        Delimiter::Comma => line.split(',').map(|t| t.trim().to_string()).collect(),
+        Delimiter::Semicolon => line.split(';').map(|t| t.trim().to_string()).collect(),
+        Delimiter::Whitespace => {
+            let mut tokens: Vec<String> = vec![];
+            for t in line.split_whitespace() {
+                match tokens.last_mut() {
+                    Some(last) if t.starts_with(['[', '(']) => last.push_str(t),
+                    _ => tokens.push(t.to_string()),
+                }
+            }
+            tokens
+        }
+    }
+}
+/// Splits a column name into its name and unit
+///
+/// A `_` suffix is a unit only if it is a known [`Unit`], e.g. `x_mm` but not `x_coord`
+fn column(name: &str) -> (String, Option<&str>) {
+    let name = name.trim();
+    let (n, u) = if let Some(i) = name.find(['[', '(']) {
+        (&name[..i], Some(name[i + 1..].trim_end_matches([']', ')'])))
+    } else if let Some((n, u)) = name
+        .rsplit_once('_')
+        .filter(|(_, u)| u.parse::<Unit>().is_ok())
+    {
+        (n, Some(u))
+    } else {
+        (name, None)
+    };
+    (n.trim().to_lowercase(), u.map(|u| u.trim()))
+}
+
+/// Header columns
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    /// `x`, `y` and `z` columns indices
+    xyz: [usize; 3],
+    /// `x`, `y` and `z` columns units
+    units: [Option<Unit>; 3],
+    /// `unit` column index
+    unit: Option<usize>,
+}
+impl Default for Columns {
+    fn default() -> Self {
+        Self {
+            xyz: [0, 1, 2],
+            units: [None; 3],
+            unit: None,
+        }
+    }
+}
+
+/// Point cloud
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PointCloud {
+    /// Points coordinates frame, `None` if it is not known
+    frame: Option<Frame>,
+    /// Points coordinates \[m\]
+    points: Vec<Vector>,
+}
+impl PointCloud {
+    /// Creates a new point cloud
+    pub fn new(frame: Frame, points: Vec<Vector>) -> Self {
+        Self {
+            frame: Some(frame),
+            points,
+        }
+    }
+    /// Returns the points coordinates frame, `None` if the point cloud was read without a frame header
+    pub fn frame(&self) -> Option<Frame> {
+        self.frame
+    }
+    /// Returns the points
+    pub fn points(&self) -> &[Vector] {
+        &self.points
+    }
+    /// Returns the number of points
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+    /// Checks if the point cloud is empty
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+    /// Returns the points as arrays
+    pub fn to_arrays(&self) -> Vec<[f64; 3]> {
+        self.points.iter().cloned().map(|v| v.into()).collect()
+    }
+    /// Reads a point cloud
+    pub fn read<R: BufRead>(reader: R) -> Result<Self, Error> {
+        let mut frame = None;
+        let mut unit = Unit::M;
+        let mut columns: Option<Columns> = None;
+        let mut delimiter: Option<Delimiter> = None;
+        let mut points = vec![];
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Some((key, value)) = comment.split_once(':') {
+                    match key.trim().to_lowercase().as_str() {
+                        "frame" => frame = Some(value.parse()?),
+                        "units" | "unit" => unit = value.parse()?,
+                        _ => (),
+                    }
+                }
+                continue;
+            }
+            if line
+                .chars()
+                .all(|c| c == ',' || c == ';' || c.is_whitespace())
+            {
+                // delimiters only
+                continue;
+            }
+            let tokens = tokenize(
+                line,
+                *delimiter.get_or_insert_with(|| Delimiter::detect(line)),
+            );
+            let names: Vec<_> = tokens.iter().map(|t| column(t)).collect();
+            let is_header = names
+                .iter()
+                .any(|(n, _)| ["x", "y", "z", "unit", "units"].contains(&n.as_str()));
+            if is_header && columns.is_none() && points.is_empty() {
+                let find = |c: &str| {
+                    names
+                        .iter()
+                        .position(|(n, _)| n == c)
+                        .ok_or_else(|| Error::PointCloud(i + 1, format!("missing column {}", c)))
+                };
+                let idx = [find("x")?, find("y")?, find("z")?];
+                let mut units = [None; 3];
+                for (u, &k) in units.iter_mut().zip(&idx) {
+                    *u = names[k].1.map(|u| u.parse()).transpose()?;
+                }
+                columns = Some(Columns {
+                    xyz: idx,
+                    units,
+                    unit: names.iter().position(|(n, _)| n == "unit" || n == "units"),
+                });
+                continue;
+            }
+            let Columns {
+                xyz: idx,
+                units,
+                unit: unit_column,
+            } = *columns.get_or_insert_with(|| {
+                // no header: skip the leading text columns
+                let k = tokens
+                    .iter()
+                    .take_while(|t| t.parse::<f64>().is_err())
+                    .count();
+                Columns {
+                    xyz: [k, k + 1, k + 2],
+                    ..Default::default()
+                }
+            });
+            let row_unit = unit_column
+                .map(|k| {
+                    tokens
+                        .get(k)
+                        .ok_or_else(|| Error::PointCloud(i + 1, "missing unit".to_string()))
+                        .and_then(|u| u.parse::<Unit>())
+                })
+                .transpose()?;
+            let mut xyz = [0f64; 3];
+            for ((v, &k), u) in xyz.iter_mut().zip(&idx).zip(&units) {
+                let value = tokens.get(k).ok_or_else(|| {
+                    Error::PointCloud(i + 1, format!("missing column #{}", k + 1))
+                })?;
+                if value.is_empty() {
+                    return Err(Error::PointCloud(i + 1, format!("empty column #{}", k + 1)));
+                }
+                *v = value
+                    .parse::<f64>()
+                    .map_err(|e| Error::PointCloud(i + 1, format!("{}: {}", value, e)))?
+                    * row_unit.or(*u).unwrap_or(unit).meters();
+            }
+            points.push(Vector::from(xyz));
+        }
+        Ok(Self { frame, points })
+    }
+    /// Reads a point cloud from a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+    /// Writes the point cloud in the given unit
+    pub fn write<W: Write>(
+        &self,
+        mut writer: W,
+        delimiter: Delimiter,
+        unit: Unit,
+    ) -> Result<(), Error> {
+        let d = match delimiter {
+            Delimiter::Comma => ",",
+            Delimiter::Semicolon => ";",
+            Delimiter::Whitespace => " ",
+        };
+        if let Some(frame) = self.frame {
+            writeln!(writer, "# frame: {}", frame)?;
+        }
+        writeln!(writer, "# units: {}", unit)?;
+        writeln!(writer, "x{d}y{d}z")?;
+        let s = unit.meters();
+        for v in &self.points {
+            writeln!(writer, "{}{d}{}{d}{}", v[0] / s, v[1] / s, v[2] / s)?;
+        }
+        Ok(())
+    }
+    /// Writes the point cloud to a file
+    pub fn to_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        delimiter: Delimiter,
+        unit: Unit,
+    ) -> Result<(), Error> {
+        self.write(BufWriter::new(File::create(path)?), delimiter, unit)
+    }
+    /// Transforms the points coordinates into the OSS
+    ///
+    /// The points of a point cloud without frame are in the OSS
+    pub fn to_oss(self) -> Self {
+        let points = match self.frame.unwrap_or_default() {
+            Frame::Oss => self.points,
+            Frame::M1(sid) => {
+                let segment = Segment::<M1>::from_id(sid);
+                self.points
+                    .into_iter()
+                    .map(|v| v.to(segment.clone()))
+                    .collect()
+            }
+            Frame::M2(sid) => {
+                let segment = Segment::<M2>::from_id(sid);
+                self.points
+                    .into_iter()
+                    .map(|v| v.to(segment.clone()))
+                    .collect()
+            }
+        };
+        Self {
+            frame: Some(Frame::Oss),
+            points,
+        }
+    }
+    /// Transforms the points coordinates into another frame
+    pub fn to_frame(self, frame: Frame) -> Self {
+        if Some(frame) == self.frame {
+            return self;
+        }
+        let points = self.to_oss().points;
+        let points = match frame {
+            Frame::Oss => points,
+            Frame::M1(sid) => {
+                let segment = Segment::<M1>::from_id(sid);
+                points.into_iter().map(|v| v.fro(segment.clone())).collect()
+            }
+            Frame::M2(sid) => {
+                let segment = Segment::<M2>::from_id(sid);
+                points.into_iter().map(|v| v.fro(segment.clone())).collect()
+            }
+        };
+        Self {
+            frame: Some(frame),
+            points,
+        }
+    }
+}
+impl From<Vec<[f64; 3]>> for PointCloud {
+    fn from(points: Vec<[f64; 3]>) -> Self {
+        Self::new(Frame::Oss, points.into_iter().map(Vector::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sid(id: u8) -> SegmentId {
+        SegmentId::new(id).unwrap()
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!("oss".parse::<Frame>().unwrap(), Frame::Oss);
+        assert_eq!("M1S3".parse::<Frame>().unwrap(), Frame::M1(sid(3)));
+        assert_eq!(" m2s7 ".parse::<Frame>().unwrap(), Frame::M2(sid(7)));
+        for frame in ["M1S8", "M12", "M1 2", "M2S", "M2S+3", "M1S2x"] {
+            assert!(frame.parse::<Frame>().is_err(), "{}", frame);
+        }
+        assert_eq!(Frame::M2(sid(4)).to_string(), "M2S4");
+    }
+
+    #[test]
+    fn read_csv_mm() {
+        let data = "# frame: M1S2\n# units: mm\nid,x,y,z\n1,1000,2000,3\n2,-1000,0,0\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.frame(), Some(Frame::M1(sid(2))));
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3e-3], [-1., 0., 0.]]);
+    }
+
+    #[test]
+    fn read_oss_frame() {
+        let cloud = PointCloud::read("# frame: oss\n1,2,3\n".as_bytes()).unwrap();
+        assert_eq!(cloud.frame(), Some(Frame::Oss));
+    }
+
+    #[test]
+    fn read_text_ids() {
+        let data = "T001,1,2,3\nT002,-1,0,0\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.], [-1., 0., 0.]]);
+        let data = "# units: mm\nP1 1000 2000 3\nP2 0 0 0\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3e-3], [0., 0., 0.]]);
+    }
+
+    #[test]
+    fn read_delimiters_only() {
+        let data = "x,y,z\n,,,\n1,2,3\n;\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.frame(), None);
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]]);
+        assert!(PointCloud::read(",,,\n".as_bytes())
+            .unwrap()
+            .points()
+            .is_empty());
+    }
+
+    #[test]
+    fn read_whitespace_units() {
+        let data = "x[mm] y[mm] z[m] unit\n1 2 3 m\n1000 2000 3\n";
+        assert!(PointCloud::read(data.as_bytes()).is_err());
+        let data = "x[mm] y_mm z(m)\n1000 2000 3\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]]);
+        let data = "x_mm y_mm z_mm z_coord\n1000 2000 3 4\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3e-3]]);
+        let data = "x y z unit\n1 2 3 m\n1000 2000 3000 mm\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]; 2]);
+    }
+
+    #[test]
+    fn read_empty_fields() {
+        let data = "x,y,z,w\n1,,2,3\n";
+        let e = PointCloud::read(data.as_bytes()).unwrap_err();
+        assert!(e.to_string().contains("empty column #2"), "{}", e);
+        let data = "x,y,z,w\n1,2,3,\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]]);
+        let data = "id;x;y;z\nP 1;1,5;2;3\n";
+        assert!(PointCloud::read(data.as_bytes()).is_err());
+        let data = "id;x;y;z\nP 1;1.5;2;3\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1.5, 2., 3.]]);
+    }
+
+    #[test]
+    fn read_parenthesized_units() {
+        let data = "x (mm),y (mm),z (m)\n1000,2000,3\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]]);
+        let data = "x (mm) y (mm) z [m]\n1000 2000 3\n";
+        let cloud = PointCloud::read(data.as_bytes()).unwrap();
+        assert_eq!(cloud.to_arrays(), vec![[1., 2., 3.]]);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let cloud = PointCloud::new(Frame::M2(sid(5)), vec![Vector::from([0.1, -0.2, 0.3])]);
+        let mut buffer = vec![];
+        cloud
+            .write(&mut buffer, Delimiter::Whitespace, Unit::Mm)
+            .unwrap();
+        let other = PointCloud::read(buffer.as_slice()).unwrap();
+        assert_eq!(other.frame(), Some(Frame::M2(sid(5))));
+        let d = other.points()[0].clone() - cloud.points()[0].clone();
+        assert!(d.norm() < 1e-15);
+    }
+
+    #[test]
+    fn frame_conversion() {
+        let cloud = PointCloud::from(vec![[1., 8., 4.], [-1., 8.5, 4.2]]);
+        let local = cloud.clone().to_frame(Frame::M1(sid(1)));
+        let m2 = local.to_frame(Frame::M2(sid(3)));
+        let oss = m2.to_frame(Frame::Oss);
+        for (a, b) in oss.points().iter().zip(cloud.points()) {
+            assert!((a.clone() - b.clone()).norm() < 1e-12);
+        }
+    }
+}
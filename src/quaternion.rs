@@ -1,16 +1,17 @@
-use crate::Vector;
-use std::cmp::PartialEq;
-use std::f64::consts::FRAC_PI_2;
-use std::fmt;
-use std::ops::{Add, Div, Mul, Sub};
+use crate::{Error, Real, Vector};
+use core::cmp::PartialEq;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::{Add, Div, Mul, Sub};
 
 /// Quaternion
 #[derive(Clone, Debug)]
-pub struct Quaternion {
-    scalar: f64,
-    vector: Vector,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion<T = f64> {
+    scalar: T,
+    vector: Vector<T>,
 }
-impl Add for Quaternion {
+impl<T: Real> Add for Quaternion<T> {
     type Output = Self;
     fn add(self, other: Self) -> Self {
         Self {
@@ -19,7 +20,7 @@ impl Add for Quaternion {
         }
     }
 }
-impl Sub for Quaternion {
+impl<T: Real> Sub for Quaternion<T> {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
         Self {
@@ -28,25 +29,86 @@ impl Sub for Quaternion {
         }
     }
 }
-impl Quaternion {
-    pub fn new<T: AsRef<[f64]>>(scalar: f64, vector: T) -> Self {
-        Self {
+impl<T: Real> Quaternion<T> {
+    /// Creates a new quaternion from the finite scalar part and the vector part
+    ///
+    /// The vector part is converted with [`Vector::try_from`]
+    pub fn new<V: AsRef<[T]>>(scalar: T, vector: V) -> Result<Self, Error> {
+        if !scalar.is_finite() {
+            return Err(Error::NonFinite);
+        }
+        Ok(Self {
             scalar,
-            vector: vector.as_ref().into(),
+            vector: Vector::try_from(vector.as_ref())?,
+        })
+    }
+    /// Creates a new pure quaternion from the vector part
+    ///
+    /// The vector part is converted with [`Vector::try_from`]
+    pub fn pure<V: AsRef<[T]>>(u: V) -> Result<Self, Error> {
+        Quaternion::new(T::zero(), u)
+    }
+    /// Creates the rotation quaternion of the finite angle `theta` around the non-zero axis `u`
+    ///
+    /// The axis is converted with [`Vector::try_from`]
+    pub fn unit<V: AsRef<[T]>>(theta: T, u: V) -> Result<Self, Error> {
+        let v = Vector::try_from(u.as_ref())?;
+        if !theta.is_finite() {
+            return Err(Error::NonFinite);
+        }
+        if v.norm() == T::zero() {
+            return Err(Error::ZeroAxis);
         }
+        Ok(Self::rotation(theta, &v))
     }
-    pub fn pure<T: AsRef<[f64]>>(u: T) -> Self {
-        Quaternion::new(0f64, u)
+    /// Creates a new quaternion from the scalar and the vector parts
+    pub(crate) fn from_parts(scalar: T, vector: Vector<T>) -> Self {
+        Self { scalar, vector }
     }
-    pub fn unit<T: AsRef<[f64]>>(theta: f64, u: T) -> Self {
-        let v = Vector::from(u.as_ref());
+    /// Creates the rotation quaternion of angle `theta` around the non-zero axis `u`
+    pub(crate) fn rotation(theta: T, u: &Vector<T>) -> Self {
+        let half = T::from_f64(0.5) * theta;
         Self {
-            scalar: (0.5 * theta).cos(),
-            vector: (0.5 * theta).sin() * &v / v.norm(),
+            scalar: half.cos(),
+            vector: u * half.sin() / u.norm(),
         }
     }
+    /// Returns the rotation quaternion of the Euler angles `(roll, pitch, yaw)`
+    ///
+    /// This is the inverse of [`Quaternion::euler_angles`]
+    pub fn from_euler_angles(roll: T, pitch: T, yaw: T) -> Self {
+        Quaternion::rotation(yaw, &Vector::k())
+            * Quaternion::rotation(pitch, &Vector::j())
+            * Quaternion::rotation(roll, &Vector::i())
+    }
+    /// Returns the 3x3 rotation matrix of a unit quaternion
+    pub fn to_rotation_matrix(&self) -> [[T; 3]; 3] {
+        let w = self.scalar;
+        let [x, y, z]: [T; 3] = self.vector.clone().into();
+        let (one, two) = (T::one(), T::one() + T::one());
+        [
+            [
+                one - two * (y * y + z * z),
+                two * (x * y - w * z),
+                two * (x * z + w * y),
+            ],
+            [
+                two * (x * y + w * z),
+                one - two * (x * x + z * z),
+                two * (y * z - w * x),
+            ],
+            [
+                two * (x * z - w * y),
+                two * (y * z + w * x),
+                one - two * (x * x + y * y),
+            ],
+        ]
+    }
     pub fn identity() -> Self {
-        Self::new(1f64, [0f64; 3])
+        Self {
+            scalar: T::one(),
+            vector: Vector::null(),
+        }
     }
     pub fn complex_conjugate(&self) -> Self {
         Self {
@@ -54,136 +116,207 @@ impl Quaternion {
             ..*self
         }
     }
-    pub fn norm_squared(&self) -> f64 {
+    pub fn norm_squared(&self) -> T {
         self.scalar * self.scalar + self.vector.norm_squared()
     }
-    pub fn norm(&self) -> f64 {
+    pub fn norm(&self) -> T {
         self.norm_squared().sqrt()
     }
     pub fn inverse(&self) -> Self {
         self.complex_conjugate() / self.norm_squared()
     }
-    pub fn vector_as_slice(&self) -> &[f64] {
+    pub fn scalar(&self) -> T {
+        self.scalar
+    }
+    pub fn vector_as_slice(&self) -> &[T] {
         self.vector.as_ref()
     }
-    pub fn euler_angles(&self) -> (f64, f64, f64) {
+    /// Returns the vector part of the quaternion
+    pub fn vector(&self) -> Vector<T> {
+        self.vector.clone()
+    }
+    /// Checks that the quaternion is a rotation, i.e. a unit quaternion, within `tolerance`
+    pub fn check_unit(&self, tolerance: T) -> Result<&Self, Error> {
+        let norm = self.norm();
+        if (norm - T::one()).abs() <= tolerance {
+            Ok(self)
+        } else {
+            Err(Error::NonUnitRotation(norm.to_f64().unwrap_or(f64::NAN)))
+        }
+    }
+    /// Creates a new quaternion from the 4 finite values `[w,x,y,z]`
+    pub fn from_wxyz(wxyz: &[T]) -> Result<Self, Error> {
+        match *wxyz {
+            [w, x, y, z] => Quaternion::new(w, [x, y, z]),
+            _ => Err(Error::QuaternionLength(wxyz.len())),
+        }
+    }
+    pub fn euler_angles(&self) -> (T, T, T) {
         let w = self.scalar;
-        let [x, y, z] = self.vector.clone().into();
+        let [x, y, z]: [T; 3] = self.vector.clone().into();
+        let (one, two) = (T::one(), T::one() + T::one());
         // roll (x-axis rotation)
-        let sinr_cosp = 2. * (w * x + y * z);
-        let cosr_cosp = 1. - 2. * (x * x + y * y);
+        let sinr_cosp = two * (w * x + y * z);
+        let cosr_cosp = one - two * (x * x + y * y);
         let roll = sinr_cosp.atan2(cosr_cosp);
 
         // pitch (y-axis rotation)
-        let sinp = (1. + 2. * (w * y - x * z)).sqrt();
-        let cosp = (1. - 2. * (w * y - x * z)).sqrt();
-        let pitch = 2. * sinp.atan2(cosp) - FRAC_PI_2;
+        let sinp = (one + two * (w * y - x * z)).sqrt();
+        let cosp = (one - two * (w * y - x * z)).sqrt();
+        let pitch = two * sinp.atan2(cosp) - T::FRAC_PI_2();
         // yaw (z-axis rotation)
-        let siny_cosp = 2. * (w * z + x * y);
-        let cosy_cosp = 1. - 2. * (y * y + z * z);
+        let siny_cosp = two * (w * z + x * y);
+        let cosy_cosp = one - two * (y * y + z * z);
         let yaw = siny_cosp.atan2(cosy_cosp);
         (roll, pitch, yaw)
     }
+    /// Spherical linear interpolation between the unit quaternions `self` (`s=0`) and `other` (`s=1`)
+    ///
+    /// The interpolation follows the shortest arc between the 2 rotations
+    pub fn slerp(&self, other: &Self, s: T) -> Self {
+        let dot = self.scalar * other.scalar + self.vector.dot(&other.vector);
+        let (dot, sign) = if dot < T::zero() {
+            (-dot, -T::one())
+        } else {
+            (dot, T::one())
+        };
+        let (a, b) = if dot > T::one() - T::epsilon().sqrt() {
+            (T::one() - s, s)
+        } else {
+            let theta = dot.acos();
+            let sin = theta.sin();
+            (
+                ((T::one() - s) * theta).sin() / sin,
+                (s * theta).sin() / sin,
+            )
+        };
+        let b = sign * b;
+        let q = Quaternion {
+            scalar: self.scalar * a + other.scalar * b,
+            vector: &self.vector * a + &other.vector * b,
+        };
+        let norm = q.norm();
+        q / norm
+    }
+    /// Converts the quaternion to another floating point type
+    pub fn cast<U: Real>(&self) -> Quaternion<U> {
+        Quaternion {
+            scalar: U::from_f64(self.scalar.into()),
+            vector: self.vector.cast(),
+        }
+    }
 }
-impl From<Vector> for Quaternion {
-    fn from(v: Vector) -> Self {
-        Quaternion::pure(v)
+impl<T: Real> From<Vector<T>> for Quaternion<T> {
+    fn from(vector: Vector<T>) -> Self {
+        Self {
+            scalar: T::zero(),
+            vector,
+        }
     }
 }
-impl From<&Vector> for Quaternion {
-    fn from(v: &Vector) -> Self {
-        Quaternion::pure(v.clone())
+impl<T: Real> From<&Vector<T>> for Quaternion<T> {
+    fn from(v: &Vector<T>) -> Self {
+        Quaternion::from(v.clone())
     }
 }
-impl From<&mut Vector> for Quaternion {
-    fn from(v: &mut Vector) -> Self {
-        Quaternion::pure(v.clone())
+impl<T: Real> From<&mut Vector<T>> for Quaternion<T> {
+    fn from(v: &mut Vector<T>) -> Self {
+        Quaternion::from(v.clone())
     }
 }
-impl From<&[f64]> for Quaternion {
-    fn from(v: &[f64]) -> Self {
-        Quaternion::pure(Vector::from(v).clone())
+impl<T: Real> TryFrom<&[T]> for Quaternion<T> {
+    type Error = Error;
+    /// Converts the finite vector `[x,y,z]` into a pure quaternion, see [`Quaternion::pure`]
+    fn try_from(v: &[T]) -> Result<Self, Self::Error> {
+        Quaternion::pure(v)
     }
 }
-impl<T: Into<Quaternion>> Mul<T> for Quaternion {
-    type Output = Quaternion;
-    fn mul(self, rhs: T) -> Quaternion {
-        let rhs: Quaternion = rhs.into();
+impl<T: Real, R: Into<Quaternion<T>>> Mul<R> for Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn mul(self, rhs: R) -> Quaternion<T> {
+        let rhs: Quaternion<T> = rhs.into();
         Quaternion {
             scalar: self.scalar * rhs.scalar - self.vector.dot(&rhs.vector),
-            vector: self.scalar * &rhs.vector
-                + rhs.scalar * &self.vector
+            vector: &rhs.vector * self.scalar
+                + &self.vector * rhs.scalar
                 + self.vector.cross(&rhs.vector),
         }
     }
 }
-impl<T: Into<Quaternion>> Mul<T> for &Quaternion {
-    type Output = Quaternion;
-    fn mul(self, rhs: T) -> Quaternion {
-        let rhs: Quaternion = rhs.into();
+impl<T: Real, R: Into<Quaternion<T>>> Mul<R> for &Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn mul(self, rhs: R) -> Quaternion<T> {
+        let rhs: Quaternion<T> = rhs.into();
         Quaternion {
             scalar: self.scalar * rhs.scalar - self.vector.dot(&rhs.vector),
-            vector: self.scalar * &rhs.vector
-                + rhs.scalar * &self.vector
+            vector: &rhs.vector * self.scalar
+                + &self.vector * rhs.scalar
                 + self.vector.cross(&rhs.vector),
         }
     }
 }
-impl Mul for &Quaternion {
-    type Output = Quaternion;
-    fn mul(self, rhs: &Quaternion) -> Quaternion {
+impl<T: Real> Mul for &Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn mul(self, rhs: &Quaternion<T>) -> Quaternion<T> {
         Quaternion {
             scalar: self.scalar * rhs.scalar - self.vector.dot(&rhs.vector),
-            vector: self.scalar * &rhs.vector
-                + rhs.scalar * &self.vector
+            vector: &rhs.vector * self.scalar
+                + &self.vector * rhs.scalar
                 + self.vector.cross(&rhs.vector),
         }
     }
 }
-impl Mul<&Quaternion> for Quaternion {
-    type Output = Quaternion;
-    fn mul(self, rhs: &Quaternion) -> Quaternion {
+impl<T: Real> Mul<&Quaternion<T>> for Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn mul(self, rhs: &Quaternion<T>) -> Quaternion<T> {
         Quaternion {
             scalar: self.scalar * rhs.scalar - self.vector.dot(&rhs.vector),
-            vector: self.scalar * &rhs.vector
-                + rhs.scalar * &self.vector
+            vector: &rhs.vector * self.scalar
+                + &self.vector * rhs.scalar
                 + self.vector.cross(&rhs.vector),
         }
     }
 }
-impl Div<f64> for &Quaternion {
-    type Output = Quaternion;
-    fn div(self, rhs: f64) -> Quaternion {
+impl<T: Real> Div<T> for &Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn div(self, rhs: T) -> Quaternion<T> {
         Quaternion {
             scalar: self.scalar / rhs,
             vector: self.vector.clone() / rhs,
         }
     }
 }
-impl Div<f64> for Quaternion {
-    type Output = Quaternion;
-    fn div(self, rhs: f64) -> Quaternion {
+impl<T: Real> Div<T> for Quaternion<T> {
+    type Output = Quaternion<T>;
+    fn div(self, rhs: T) -> Quaternion<T> {
         Quaternion {
             scalar: self.scalar / rhs,
             vector: self.vector / rhs,
         }
     }
 }
-impl Mul<&Quaternion> for f64 {
-    type Output = Quaternion;
-    fn mul(self, rhs: &Quaternion) -> Quaternion {
-        Quaternion {
-            scalar: self * rhs.scalar,
-            vector: self * &rhs.vector,
-        }
-    }
+macro_rules! scalar_mul {
+    ($($t:ty),*) => {
+        $(
+            impl Mul<&Quaternion<$t>> for $t {
+                type Output = Quaternion<$t>;
+                fn mul(self, rhs: &Quaternion<$t>) -> Quaternion<$t> {
+                    Quaternion {
+                        scalar: self * rhs.scalar,
+                        vector: self * &rhs.vector,
+                    }
+                }
+            }
+        )*
+    };
 }
-impl PartialEq for Quaternion {
+scalar_mul!(f32, f64);
+impl<T: Real> PartialEq for Quaternion<T> {
     fn eq(&self, other: &Self) -> bool {
         self.scalar == other.scalar && self.vector == other.vector
     }
 }
-impl fmt::Display for Quaternion {
+impl<T: Real> fmt::Display for Quaternion<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:.6} + {}", self.scalar, self.vector)
     }
@@ -191,57 +324,146 @@ impl fmt::Display for Quaternion {
 
 #[cfg(test)]
 mod tests {
-    use std::f64::EPSILON;
-
     use super::*;
 
+    #[cfg(feature = "std")]
     #[test]
     fn quaternion_new() {
-        let a = Quaternion::new(3., [1., -2., 1.]);
-        let v = Quaternion::new(3., Vector::from([1., -2., 1.]));
+        let a = Quaternion::new(3., [1., -2., 1.]).unwrap();
+        let v = Quaternion::new(3., Vector::from([1., -2., 1.])).unwrap();
         println!("a: {}", a);
         println!("v: {}", v);
     }
 
     #[test]
     fn quaternion_addition() {
-        let p = Quaternion::new(3., [1., -2., 1.]);
-        let q = Quaternion::new(2., [-1., 2., 3.]);
+        let p = Quaternion::new(3., [1., -2., 1.]).unwrap();
+        let q = Quaternion::new(2., [-1., 2., 3.]).unwrap();
         let s = p + q;
-        assert_eq!(s, Quaternion::new(5., [0., 0., 4.]));
+        assert_eq!(s, Quaternion::new(5., [0., 0., 4.]).unwrap());
+    }
+    #[test]
+    fn slerp() {
+        let p = Quaternion::unit(0.2f64, Vector::k()).unwrap();
+        let q = Quaternion::unit(1.2, Vector::k()).unwrap();
+        let (_, _, y) = p.slerp(&q, 0.3).euler_angles();
+        assert!((y - 0.5).abs() < 1e-15);
+        let m = Quaternion::new(-q.scalar(), -q.vector()).unwrap();
+        assert!((p.slerp(&m, 0.3) - p.slerp(&q, 0.3)).norm() < 1e-15);
+        assert!((p.slerp(&p, 0.7) - p).norm() < 1e-15);
     }
     #[test]
     fn quaternion_multiplication() {
-        let p = Quaternion::new(3., [1., -2., 1.]);
-        let q = Quaternion::new(2., [-1., 2., 3.]);
+        let p = Quaternion::new(3., [1., -2., 1.]).unwrap();
+        let q = Quaternion::new(2., [-1., 2., 3.]).unwrap();
         let m = &p * &q;
-        assert_eq!(m, Quaternion::new(8., [-9., -2., 11.]));
+        assert_eq!(m, Quaternion::new(8., [-9., -2., 11.]).unwrap());
     }
+    #[cfg(feature = "std")]
     #[test]
     fn euler_angles_roll() {
-        let q = Quaternion::unit(10f64.to_radians(), Vector::i());
+        let q = Quaternion::unit(10f64.to_radians(), Vector::i()).unwrap();
         let (r, p, y) = q.euler_angles();
         println!("roll : {}deg", r.to_degrees());
         println!("pitch: {}deg", p.to_degrees());
         println!("yaw  : {}deg", y.to_degrees());
-        assert!((r.to_degrees() - 10f64).abs() < 1e2 * EPSILON)
+        assert!((r.to_degrees() - 10f64).abs() < 1e2 * f64::EPSILON)
     }
+    #[cfg(feature = "std")]
     #[test]
     fn euler_angles_pitch() {
-        let q = Quaternion::unit(-20f64.to_radians(), Vector::j());
+        let q = Quaternion::unit(-20f64.to_radians(), Vector::j()).unwrap();
         let (r, p, y) = q.euler_angles();
         println!("roll : {}deg", r.to_degrees());
         println!("pitch: {}deg", p.to_degrees());
         println!("yaw  : {}deg", y.to_degrees());
-        assert!((p.to_degrees() - -20f64).abs() < 1e2 * EPSILON)
+        assert!((p.to_degrees() - -20f64).abs() < 1e2 * f64::EPSILON)
     }
+    #[cfg(feature = "std")]
     #[test]
     fn euler_angles_yaw() {
-        let q = Quaternion::unit(30f64.to_radians(), Vector::k());
+        let q = Quaternion::unit(30f64.to_radians(), Vector::k()).unwrap();
         let (r, p, y) = q.euler_angles();
         println!("roll : {}deg", r.to_degrees());
         println!("pitch: {}deg", p.to_degrees());
         println!("yaw  : {}deg", y.to_degrees());
-        assert!((y.to_degrees() - 30f64).abs() < 1e2 * EPSILON)
+        assert!((y.to_degrees() - 30f64).abs() < 1e2 * f64::EPSILON)
+    }
+    #[test]
+    fn rotation_matrix() {
+        let q = Quaternion::unit(30f64.to_radians(), Vector::from([1., 2., -1.])).unwrap();
+        let r = q.to_rotation_matrix();
+        let u = Vector::from([0.3, -1., 2.]);
+        let v = (&q * u.clone() * q.complex_conjugate()).vector();
+        for i in 0..3 {
+            let ri = Vector::from(r[i]);
+            assert!((ri.dot(&u) - v[i]).abs() < 1e-12);
+        }
+    }
+    #[test]
+    fn quaternion_try_from() {
+        let q = Quaternion::from_wxyz(&[0.5, 0.5, -0.5, 0.5]).unwrap();
+        assert!(q.check_unit(1e-12).is_ok());
+        assert!(matches!(
+            Quaternion::new(2., [0., 0., 0.]).unwrap().check_unit(1e-12),
+            Err(Error::NonUnitRotation(_))
+        ));
+        assert!(matches!(
+            Quaternion::from_wxyz(&[1., 0., 0.]),
+            Err(Error::QuaternionLength(3))
+        ));
+        assert!(matches!(
+            Quaternion::from_wxyz(&[1., 0., f64::INFINITY, 0.]),
+            Err(Error::NonFinite)
+        ));
+        // a 3 elements slice is a pure quaternion
+        assert_eq!(
+            Quaternion::try_from(&[1., 2., 3.][..]).unwrap(),
+            Quaternion::pure([1., 2., 3.]).unwrap()
+        );
+        assert!(matches!(
+            Quaternion::try_from(&[0.5, 0.5, -0.5, 0.5][..]),
+            Err(Error::VectorLength(4))
+        ));
+    }
+    #[test]
+    fn quaternion_checks() {
+        assert!(matches!(
+            Quaternion::new(1., &[1., 0., 0., 1.][..]),
+            Err(Error::VectorLength(4))
+        ));
+        assert!(matches!(
+            Quaternion::pure(&[1.][..]),
+            Err(Error::VectorLength(1))
+        ));
+        assert!(matches!(
+            Quaternion::new(f64::NAN, [0., 0., 0.]),
+            Err(Error::NonFinite)
+        ));
+        assert!(matches!(
+            Quaternion::unit(f64::INFINITY, Vector::j()),
+            Err(Error::NonFinite)
+        ));
+        assert!(matches!(
+            Quaternion::unit(0.1, [0., 0., 0.]),
+            Err(Error::ZeroAxis)
+        ));
+        assert_eq!(
+            Quaternion::unit(0.1, [0., 2., 0.]).unwrap(),
+            Quaternion::unit(0.1, Vector::j()).unwrap()
+        );
+    }
+    #[test]
+    fn euler_angles_f32() {
+        let q = Quaternion::unit(30f32.to_radians(), Vector::<f32>::k()).unwrap();
+        let (_, _, y) = q.euler_angles();
+        assert!((y.to_degrees() - 30f32).abs() < 1e2 * f32::EPSILON);
+        assert!((q.cast::<f64>().norm() - 1.).abs() < 1e-6);
+    }
+    #[test]
+    fn euler_angles_roundtrip() {
+        let (r, p, y): (f64, f64, f64) = (0.1, -0.2, 0.3);
+        let (rr, pp, yy) = Quaternion::from_euler_angles(r, p, y).euler_angles();
+        assert!((r - rr).abs() < 1e-12 && (p - pp).abs() < 1e-12 && (y - yy).abs() < 1e-12);
     }
 }
@@ -0,0 +1,212 @@
+//! Segment rigid body motions
+//!
+//! The rigid body motions of a segment are given in the segment coordinate system as
+//! the translations `[Tx,Ty,Tz]` and the Euler angles `[Rx,Ry,Rz]` of [`Quaternion::euler_angles`].
+//! The rigid body motions of a mirror are the 7 segment rigid body motions concatenated
+//! in a 42 elements vector, as returned by [`Mirror::tiptilt_2_rigidbodymotions`](crate::Mirror::tiptilt_2_rigidbodymotions).
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::{Error, Quaternion, Vector};
+#[cfg(feature = "alloc")]
+use crate::{Gmt, Mirror, Segment, SegmentTrait, Transform};
+
+/// Segment rigid body motions
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rbm {
+    /// Translations `[Tx,Ty,Tz]` \[m\]
+    pub translation: [f64; 3],
+    /// Euler angles `[Rx,Ry,Rz]` \[rd\]
+    pub rotation: [f64; 3],
+}
+impl Rbm {
+    /// Creates new rigid body motions
+    pub fn new(translation: [f64; 3], rotation: [f64; 3]) -> Self {
+        Self {
+            translation,
+            rotation,
+        }
+    }
+    /// Creates new rigid body motions from the translations and a rotation [`Quaternion`]
+    ///
+    /// The quaternion norm must be 1 within 1e-9
+    pub fn from_quaternion(translation: [f64; 3], q: &Quaternion) -> Result<Self, Error> {
+        let (r, p, y) = q.check_unit(1e-9)?.euler_angles();
+        Ok(Self::new(translation, [r, p, y]))
+    }
+    /// Returns the rotation as a [`Quaternion`]
+    pub fn quaternion(&self) -> Quaternion {
+        let [r, p, y] = self.rotation;
+        Quaternion::from_euler_angles(r, p, y)
+    }
+    /// Applies the rigid body motions to the point `v` in the segment coordinates
+    pub fn apply(&self, v: Vector) -> Vector {
+        let q = self.quaternion();
+        (&q * v * q.complex_conjugate()).vector() + Vector::from(self.translation)
+    }
+    /// Applies the rotation to the vector `v` in the segment coordinates
+    pub fn rotate(&self, v: Vector) -> Vector {
+        let q = self.quaternion();
+        (&q * v * q.complex_conjugate()).vector()
+    }
+    /// Returns the rigid body motions `self` followed by the rigid body motions `other`
+    pub fn compose(&self, other: &Rbm) -> Rbm {
+        let q = other.quaternion() * self.quaternion();
+        let (r, p, y) = q.euler_angles();
+        let t = other.apply(Vector::from(self.translation));
+        Rbm::new([t[0], t[1], t[2]], [r, p, y])
+    }
+    /// Splits the 42 mirror rigid body motions into the 7 segment rigid body motions
+    #[cfg(feature = "alloc")]
+    pub fn from_mirror(rbm: &[f64]) -> Result<Vec<Rbm>, Error> {
+        if rbm.len() != 42 {
+            return Err(Error::RbmLength(rbm.len(), 42));
+        }
+        rbm.chunks(6).map(Rbm::try_from).collect()
+    }
+    /// Concatenates the segment rigid body motions into the mirror rigid body motions
+    #[cfg(feature = "alloc")]
+    pub fn to_mirror(rbms: &[Rbm]) -> Vec<f64> {
+        rbms.iter().flat_map(|rbm| <[f64; 6]>::from(*rbm)).collect()
+    }
+}
+#[cfg(feature = "alloc")]
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the 7 segment rigid body motions of the mirror `global` rigid body motions
+    ///
+    /// The `global` rigid body motions are given in the OSS with the rotations about the
+    /// center segment origin
+    pub fn global_2_rigidbodymotions(global: &Rbm) -> Vec<Rbm> {
+        Self::rotation_2_rigidbodymotions(&global.quaternion(), &Vector::from(global.translation))
+    }
+    /// Returns the 7 segment rigid body motions of the mirror rotation `q_g` about the
+    /// center segment origin followed by the translation `t_g`, both in the OSS
+    pub(crate) fn rotation_2_rigidbodymotions(q_g: &Quaternion, t_g: &Vector) -> Vec<Rbm> {
+        let v7 = Vector::null().to(Self::center_segment());
+        Self::segments()
+            .map(|segment| {
+                let v = Vector::null().to(segment.clone()) - &v7;
+                let t =
+                    ((q_g * v * q_g.complex_conjugate()).vector() + &v7 + t_g).fro(segment.clone());
+                let (r, p, y) = segment
+                    .rotation()
+                    .map_or(q_g.clone(), |q_s| q_s.complex_conjugate() * q_g * q_s)
+                    .euler_angles();
+                Rbm::new([t[0], t[1], t[2]], [r, p, y])
+            })
+            .collect()
+    }
+    /// Returns the 7 segment rigid body motions that move the nominal segments onto the
+    /// segments returned by `reshape`
+    pub(crate) fn reshape_2_rigidbodymotions<F>(reshape: F) -> Vec<Rbm>
+    where
+        F: Fn(&Segment<M>) -> Segment<M>,
+    {
+        Self::segments()
+            .map(|segment| Rbm::from(&(segment.pose().inverse() * reshape(&segment).pose())))
+            .collect()
+    }
+}
+impl From<[f64; 6]> for Rbm {
+    fn from(v: [f64; 6]) -> Self {
+        Self {
+            translation: [v[0], v[1], v[2]],
+            rotation: [v[3], v[4], v[5]],
+        }
+    }
+}
+impl TryFrom<&[f64]> for Rbm {
+    type Error = Error;
+    fn try_from(v: &[f64]) -> Result<Self, Self::Error> {
+        <[f64; 6]>::try_from(v)
+            .map(Rbm::from)
+            .map_err(|_| Error::RbmLength(v.len(), 6))
+    }
+}
+impl From<Rbm> for [f64; 6] {
+    fn from(rbm: Rbm) -> Self {
+        let [tx, ty, tz] = rbm.translation;
+        let [rx, ry, rz] = rbm.rotation;
+        [tx, ty, tz, rx, ry, rz]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "alloc")]
+    use crate::{Mirror, M1};
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn mirror_roundtrip() {
+        let rbm = Mirror::<M1>::tiptilt_2_rigidbodymotions((1e-5, -2e-5));
+        let rbms = Rbm::from_mirror(&rbm).unwrap();
+        assert_eq!(rbms.len(), 7);
+        assert_eq!(Rbm::to_mirror(&rbms), rbm);
+        assert!(Rbm::from_mirror(&rbm[..40]).is_err());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn global_tiptilt() {
+        let global = Rbm::new([0.; 3], [0., -2e-5, 0.]);
+        let rbm = Rbm::to_mirror(&Mirror::<M1>::global_2_rigidbodymotions(&global));
+        let tt = Mirror::<M1>::tiptilt_2_rigidbodymotions((0., -2e-5));
+        for (a, b) in rbm.iter().zip(tt.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        let global = Rbm::new([1e-3, 0., 0.], [0.; 3]);
+        for (segment, rbm) in
+            Mirror::<M1>::segments().zip(Mirror::<M1>::global_2_rigidbodymotions(&global))
+        {
+            let t = Vector::from(rbm.translation).vtov(segment);
+            assert!((t[0] - 1e-3).abs() < 1e-12 && t[1].abs() < 1e-12 && t[2].abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn compose() {
+        let a = Rbm::new([1e-3, 0., 2e-3], [1e-4, 0., -3e-4]);
+        let b = Rbm::new([0., -1e-3, 0.], [0., 2e-4, 1e-4]);
+        let v = Vector::from([0.5, -1., 0.2]);
+        let u = a.compose(&b).apply(v.clone()) - b.apply(a.apply(v));
+        assert!(u.norm() < 1e-15);
+    }
+
+    #[test]
+    fn quaternion_roundtrip() {
+        let rbm = Rbm::new([1e-6, 0., -2e-6], [1e-5, -2e-5, 3e-5]);
+        let other = Rbm::from_quaternion(rbm.translation, &rbm.quaternion()).unwrap();
+        for (a, b) in rbm.rotation.iter().zip(other.rotation.iter()) {
+            assert!((a - b).abs() < 1e-15);
+        }
+        let q = Quaternion::new(1.1, [0., 0., 0.]).unwrap();
+        assert!(matches!(
+            Rbm::from_quaternion([0.; 3], &q),
+            Err(Error::NonUnitRotation(_))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let rbm = Rbm::new([0.1, -1e-7, 1. / 3.], [core::f64::consts::PI, 0., -2e-9]);
+        let json = serde_json::to_string(&rbm).unwrap();
+        assert_eq!(serde_json::from_str::<Rbm>(&json).unwrap(), rbm);
+        let q = rbm.quaternion();
+        let json = serde_json::to_string(&q).unwrap();
+        assert_eq!(serde_json::from_str::<Quaternion>(&json).unwrap(), q);
+        let v = Vector::from([1. / 7., 0., -3.]);
+        assert_eq!(
+            serde_json::to_string(&v).unwrap(),
+            "[0.14285714285714285,0.0,-3.0]"
+        );
+    }
+}
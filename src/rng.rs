@@ -0,0 +1,71 @@
+//! Seedable pseudo-random number generator (xoshiro256**)
+
+/// Pseudo-random number generator
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    s: [u64; 4],
+}
+impl Rng {
+    /// Creates a new generator from the `seed`
+    ///
+    /// The state is initialized with the splitmix64 sequence of the seed
+    pub fn seed_from_u64(seed: u64) -> Self {
+        let mut x = seed;
+        let mut s = [0u64; 4];
+        for si in s.iter_mut() {
+            x = x.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            *si = z ^ (z >> 31);
+        }
+        Self { s }
+    }
+    /// Returns the next random 64 bits
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+        result
+    }
+    /// Returns a random number uniformly distributed in [0,1)
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+    /// Returns a random number normally distributed with zero mean and unit variance
+    ///
+    /// The number is drawn with the Box-Muller transform
+    pub fn normal(&mut self) -> f64 {
+        let u = 1f64 - self.uniform();
+        let v = self.uniform();
+        (-2f64 * u.ln()).sqrt() * (2f64 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reproducible() {
+        let mut a = Rng::seed_from_u64(7);
+        let mut b = Rng::seed_from_u64(7);
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+        let m = (0..10_000).map(|_| a.uniform()).sum::<f64>() / 1e4;
+        assert!((m - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn normal() {
+        let mut rng = Rng::seed_from_u64(11);
+        let x: Vec<f64> = (0..20_000).map(|_| rng.normal()).collect();
+        let m = x.iter().sum::<f64>() / x.len() as f64;
+        let v = x.iter().map(|x| (x - m).powi(2)).sum::<f64>() / x.len() as f64;
+        assert!(m.abs() < 0.02 && (v - 1.).abs() < 0.03);
+    }
+}
@@ -0,0 +1,159 @@
+//! Segment pupil sampling
+//!
+//! The samples are drawn in the x-y plane of the segment coordinate system, within the segment
+//! clear [`Aperture`](crate::Aperture), and their z coordinate follows the segment surface.
+
+use std::f64::consts::PI;
+
+use crate::{rng::Rng, Gmt, Mirror, Segment, SegmentTrait, Transform, Vector};
+
+/// Segment sampling patterns
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sampling {
+    /// Rectangular grid of `n`x`n` cells across the segment outer diameter, clipped to the aperture
+    Grid(usize),
+    /// Polar grid of `rings` concentric rings with `azimuths` samples each
+    Polar { rings: usize, azimuths: usize },
+    /// Fibonacci spiral of `n` samples
+    Fibonacci(usize),
+    /// `n` random samples uniformly distributed over the aperture
+    Random { n: usize, seed: u64 },
+}
+
+/// Segment samples
+#[derive(Debug, Clone, Default)]
+pub struct Samples {
+    /// Samples in the segment coordinates
+    pub local: Vec<Vector>,
+    /// Samples in the OSS
+    pub oss: Vec<Vector>,
+}
+impl Samples {
+    /// Returns the number of samples
+    pub fn len(&self) -> usize {
+        self.local.len()
+    }
+    /// Checks if there are no samples
+    pub fn is_empty(&self) -> bool {
+        self.local.is_empty()
+    }
+}
+
+impl<M: Gmt> Segment<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the segment samples (x,y) coordinates
+    fn sample_xy(&self, sampling: &Sampling) -> Vec<(f64, f64)> {
+        let aperture = self.aperture();
+        let (r_in, r_out) = (aperture.inner_radius(), aperture.outer_radius());
+        // radius that uniformly samples the area of the annulus for u in [0,1]
+        let area_radius = |u: f64| (r_in * r_in + (r_out * r_out - r_in * r_in) * u).sqrt();
+        match *sampling {
+            Sampling::Grid(n) => {
+                let d = 2. * r_out / n as f64;
+                (0..n)
+                    .flat_map(|i| (0..n).map(move |j| (i, j)))
+                    .map(|(i, j)| (-r_out + (j as f64 + 0.5) * d, -r_out + (i as f64 + 0.5) * d))
+                    .filter(|&(x, y)| aperture.contains(x, y))
+                    .collect()
+            }
+            Sampling::Polar { rings, azimuths } => (0..rings)
+                .flat_map(|i| {
+                    let r = r_in + (r_out - r_in) * (i as f64 + 0.5) / rings as f64;
+                    (0..azimuths).map(move |j| {
+                        let (s, c) = (2. * PI * j as f64 / azimuths as f64).sin_cos();
+                        (r * c, r * s)
+                    })
+                })
+                .collect(),
+            Sampling::Fibonacci(n) => {
+                let golden_angle = PI * (3. - 5f64.sqrt());
+                (0..n)
+                    .map(|k| {
+                        let r = area_radius((k as f64 + 0.5) / n as f64);
+                        let (s, c) = (k as f64 * golden_angle).sin_cos();
+                        (r * c, r * s)
+                    })
+                    .collect()
+            }
+            Sampling::Random { n, seed } => {
+                let mut rng = Rng::seed_from_u64(seed);
+                (0..n)
+                    .map(|_| {
+                        let r = area_radius(rng.uniform());
+                        let (s, c) = (2. * PI * rng.uniform()).sin_cos();
+                        (r * c, r * s)
+                    })
+                    .collect()
+            }
+        }
+    }
+    /// Samples the segment surface
+    ///
+    /// The samples are returned both in the segment coordinates and in the OSS
+    pub fn sample(&self, sampling: &Sampling) -> Samples {
+        let local: Vec<Vector> = self
+            .sample_xy(sampling)
+            .into_iter()
+            .map(|(x, y)| Vector::from([x, y, self.surface(x, y)]))
+            .collect();
+        let oss = local.iter().map(|v| v.clone().to(self.clone())).collect();
+        Samples { local, oss }
+    }
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Samples the surface of the 7 segments
+    pub fn sample(sampling: &Sampling) -> Vec<Samples> {
+        Self::segments()
+            .map(|segment| segment.sample(sampling))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[test]
+    fn m1_grid() {
+        let samples = Mirror::<M1>::sample(&Sampling::Grid(21));
+        for (sid, s) in (1..=7).zip(&samples) {
+            let segment = Segment::<M1>::new(sid).unwrap();
+            assert!(s.local.iter().all(|v| segment.contains(v[0], v[1])));
+            assert!(s
+                .oss
+                .iter()
+                .all(|v| (v[2] - segment.parent_surface(v[0], v[1])).abs() < 1e-9));
+        }
+        assert!(samples[6].len() < samples[0].len());
+    }
+
+    #[test]
+    fn m2_patterns() {
+        let segment = Segment::<M2>::new(3).unwrap();
+        let polar = segment.sample(&Sampling::Polar {
+            rings: 4,
+            azimuths: 12,
+        });
+        assert_eq!(polar.len(), 48);
+        let fibonacci = segment.sample(&Sampling::Fibonacci(100));
+        assert_eq!(fibonacci.len(), 100);
+        assert!(fibonacci.local.iter().all(|v| segment.contains(v[0], v[1])));
+    }
+
+    #[test]
+    fn random_reproducible() {
+        let segment = Segment::<M1>::new(7).unwrap();
+        let sampling = Sampling::Random { n: 50, seed: 1 };
+        let a = segment.sample(&sampling);
+        let b = segment.sample(&sampling);
+        assert!(a.oss.iter().zip(&b.oss).all(|(a, b)| a == b));
+        assert!(a.local.iter().all(|v| segment.contains(v[0], v[1])));
+    }
+}
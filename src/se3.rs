@@ -0,0 +1,290 @@
+//! Rigid body transforms of the special Euclidean group SE(3)
+//!
+//! A [`Se3`] transform maps the point `v` to `q v q* + t` with the unit quaternion `q`
+//! and the translation `t`, like [`Rbm::apply`] and [`Transform::to`](crate::Transform::to).
+//! The twist coordinates of [`Se3::exp`] and [`Se3::log`] are ordered as the rigid body motions,
+//! `[vx,vy,vz,wx,wy,wz]`, so that to first order the twist of small rigid body motions
+//! is the vector `[Tx,Ty,Tz,Rx,Ry,Rz]`.
+
+use core::ops::Mul;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+use crate::{DualQuaternion, Error, Gmt, Quaternion, Rbm, Segment, SegmentTrait, Vector};
+
+/// Rigid body transform
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Se3 {
+    rotation: Quaternion,
+    translation: Vector,
+}
+impl Se3 {
+    /// Creates a new transform from a rotation [`Quaternion`] and a translation
+    ///
+    /// The quaternion norm must be 1 within 1e-9
+    pub fn new<V: Into<Vector>>(rotation: Quaternion, translation: V) -> Result<Self, Error> {
+        rotation.check_unit(1e-9)?;
+        Ok(Self {
+            rotation,
+            translation: translation.into(),
+        })
+    }
+    /// Returns the identity transform
+    pub fn identity() -> Self {
+        Self {
+            rotation: Quaternion::identity(),
+            translation: Vector::null(),
+        }
+    }
+    /// Returns the transform from the coordinates of a segment to the OSS
+    ///
+    /// This is the transform of [`Transform::to`](crate::Transform::to)
+    pub fn segment<M>(segment: &Segment<M>) -> Self
+    where
+        M: Gmt,
+        Segment<M>: SegmentTrait,
+    {
+        Self {
+            rotation: segment.rotation().unwrap_or_else(Quaternion::identity),
+            translation: segment.translation(),
+        }
+    }
+    /// Returns the rotation quaternion
+    pub fn rotation(&self) -> &Quaternion {
+        &self.rotation
+    }
+    /// Returns the translation
+    pub fn translation(&self) -> &Vector {
+        &self.translation
+    }
+    /// Applies the transform to the point `v`
+    pub fn apply(&self, v: Vector) -> Vector {
+        self.rotate(v) + &self.translation
+    }
+    /// Applies the rotation to the vector `v`
+    pub fn rotate(&self, v: Vector) -> Vector {
+        (&self.rotation * v * self.rotation.complex_conjugate()).vector()
+    }
+    /// Returns the inverse transform
+    pub fn inverse(&self) -> Self {
+        let rotation = self.rotation.complex_conjugate();
+        let translation = -(&rotation * self.translation.clone() * &self.rotation).vector();
+        Self {
+            rotation,
+            translation,
+        }
+    }
+    /// Returns the transform `self` followed by the transform `other`, i.e. `other * self`
+    pub fn compose(&self, other: &Se3) -> Se3 {
+        other * self
+    }
+    /// Returns the transform of the twist `[vx,vy,vz,wx,wy,wz]`
+    ///
+    /// See [`DualQuaternion::exp`]
+    pub fn exp(twist: [f64; 6]) -> Self {
+        Self::from(&DualQuaternion::exp(twist))
+    }
+    /// Returns the twist `[vx,vy,vz,wx,wy,wz]` of the transform
+    ///
+    /// The rotation angle of the twist is in the range \[0,π\], see [`DualQuaternion::log`]
+    pub fn log(&self) -> [f64; 6] {
+        DualQuaternion::from(self).log()
+    }
+    /// Returns the 6x6 adjoint matrix of the transform
+    ///
+    /// The adjoint maps the twists as `exp(Ad ξ) = T exp(ξ) T⁻¹`
+    pub fn adjoint(&self) -> [[f64; 6]; 6] {
+        let r = self.rotation.to_rotation_matrix();
+        let t = &self.translation;
+        let mut adjoint = [[0f64; 6]; 6];
+        for i in 0..3 {
+            for j in 0..3 {
+                adjoint[i][j] = r[i][j];
+                adjoint[i + 3][j + 3] = r[i][j];
+                // [t]x R
+                let (k, l) = ((i + 1) % 3, (i + 2) % 3);
+                adjoint[i][j + 3] = t[k] * r[l][j] - t[l] * r[k][j];
+            }
+        }
+        adjoint
+    }
+    /// Interpolates between `self` (`s=0`) and `other` (`s=1`) along the geodesic
+    pub fn interpolate(&self, other: &Se3, s: f64) -> Self {
+        let xi = (&self.inverse() * other).log();
+        self * &Self::exp(xi.map(|x| s * x))
+    }
+    /// Returns the mean of the transforms, `None` if `poses` is empty
+    ///
+    /// The mean is the exponential barycenter of the transforms: the fixed point `m` of
+    /// `m = m exp(mean(log(m^-1 pose)))`, found by iterating from the first pose.
+    /// It is a local solution that depends on the first pose as initial guess
+    pub fn mean(poses: &[Se3]) -> Option<Self> {
+        let mut mean = poses.first()?.clone();
+        let n = poses.len() as f64;
+        for _ in 0..50 {
+            let inverse = mean.inverse();
+            let mut xi = [0f64; 6];
+            for pose in poses {
+                let d = (&inverse * pose).log();
+                xi.iter_mut().zip(d).for_each(|(x, d)| *x += d / n);
+            }
+            mean = &mean * &Self::exp(xi);
+            if xi.iter().map(|x| x * x).sum::<f64>() < 1e-30 {
+                break;
+            }
+        }
+        Some(mean)
+    }
+}
+impl Mul for &Se3 {
+    type Output = Se3;
+    /// Returns the transform `rhs` followed by the transform `self`
+    fn mul(self, rhs: &Se3) -> Se3 {
+        Se3 {
+            rotation: &self.rotation * &rhs.rotation,
+            translation: self.apply(rhs.translation.clone()),
+        }
+    }
+}
+impl Mul for Se3 {
+    type Output = Se3;
+    /// Returns the transform `rhs` followed by the transform `self`
+    fn mul(self, rhs: Se3) -> Se3 {
+        &self * &rhs
+    }
+}
+impl From<&Se3> for DualQuaternion {
+    fn from(se3: &Se3) -> Self {
+        Self::compose(se3.rotation.clone(), se3.translation.clone())
+    }
+}
+impl From<&DualQuaternion> for Se3 {
+    fn from(dq: &DualQuaternion) -> Self {
+        Self {
+            rotation: dq.real().clone(),
+            translation: dq.translation_vector(),
+        }
+    }
+}
+impl From<&Rbm> for Se3 {
+    fn from(rbm: &Rbm) -> Self {
+        Self {
+            rotation: rbm.quaternion(),
+            translation: Vector::from(rbm.translation),
+        }
+    }
+}
+impl From<Rbm> for Se3 {
+    fn from(rbm: Rbm) -> Self {
+        Self::from(&rbm)
+    }
+}
+impl From<&Se3> for Rbm {
+    fn from(se3: &Se3) -> Self {
+        let (r, p, y) = se3.rotation.euler_angles();
+        Rbm::new(se3.translation.clone().into(), [r, p, y])
+    }
+}
+impl From<Se3> for Rbm {
+    fn from(se3: Se3) -> Self {
+        Self::from(&se3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mirror, Transform, M1, M2};
+
+    fn close(a: &Se3, b: &Se3, tol: f64) -> bool {
+        (&a.inverse() * b).log().iter().all(|x| x.abs() < tol)
+    }
+
+    #[test]
+    fn exp_log() {
+        for xi in [
+            [0.1, -0.2, 0.3, 0.4, -0.5, 0.6],
+            [1e-3, 0., 2e-3, 1e-7, 0., -2e-7],
+            [0.5, 0.5, 0., 0., 0., 0.],
+            [0., 0., 1., 0., 3., 0.],
+        ] {
+            let log = Se3::exp(xi).log();
+            assert!(
+                xi.iter().zip(&log).all(|(a, b)| (a - b).abs() < 1e-12),
+                "{:?}",
+                log
+            );
+        }
+    }
+
+    #[test]
+    fn group() {
+        let a = Se3::exp([0.1, -0.2, 0.3, 0.4, -0.5, 0.6]);
+        let b = Se3::exp([-1., 0.5, 0., 0., 0.2, -0.1]);
+        let v = Vector::from([0.5, -1., 0.2]);
+        assert!(((&a * &b).apply(v.clone()) - a.apply(b.apply(v.clone()))).norm() < 1e-14);
+        assert!((a.inverse().apply(a.apply(v.clone())) - v).norm() < 1e-14);
+        assert!(close(&(&a * &a.inverse()), &Se3::identity(), 1e-14));
+    }
+
+    #[test]
+    fn rbm() {
+        let a = Rbm::new([1e-3, 0., 2e-3], [1e-4, 0., -3e-4]);
+        let b = Rbm::new([0., -1e-3, 0.], [0., 2e-4, 1e-4]);
+        let c = Rbm::from(Se3::from(a).compose(&Se3::from(b)));
+        let (c, ab): ([f64; 6], [f64; 6]) = (c.into(), a.compose(&b).into());
+        assert!(c.iter().zip(&ab).all(|(c, ab)| (c - ab).abs() < 1e-15));
+        // small rigid body motions linearization
+        let rbm = Rbm::new([1e-6, -2e-6, 3e-6], [1e-6, 2e-6, -1e-6]);
+        let xi = Se3::from(rbm).log();
+        let rbm: [f64; 6] = rbm.into();
+        assert!(xi.iter().zip(&rbm).all(|(x, r)| (x - r).abs() < 1e-11));
+    }
+
+    #[test]
+    fn segment() {
+        let v = Vector::from([0.1, -0.2, 0.05]);
+        for segment in Mirror::<M1>::segments() {
+            let pose = Se3::segment(&segment);
+            assert!((pose.apply(v.clone()) - v.clone().to(segment.clone())).norm() < 1e-14);
+            assert!((pose.inverse().apply(v.clone()) - v.clone().fro(segment)).norm() < 1e-14);
+        }
+        let segment = Mirror::<M2>::center_segment();
+        let rbm = Rbm::new([1e-6, 0., 0.], [0., 1e-6, 0.]);
+        let u = rbm.apply(v.clone()).to(segment.clone());
+        let w = (Se3::segment(&segment) * Se3::from(rbm)).apply(v);
+        assert!((u - w).norm() < 1e-14);
+    }
+
+    #[test]
+    fn adjoint() {
+        let t = Se3::exp([0.1, -0.2, 0.3, 0.4, -0.5, 0.6]);
+        let xi = [1e-2, 2e-2, -1e-2, 0.1, 0., -0.2];
+        let ad = t.adjoint();
+        let mut axi = [0f64; 6];
+        for (a, row) in axi.iter_mut().zip(&ad) {
+            *a = row.iter().zip(&xi).map(|(r, x)| r * x).sum();
+        }
+        let lhs = &(&t * &Se3::exp(xi)) * &t.inverse();
+        assert!(close(&lhs, &Se3::exp(axi), 1e-14));
+    }
+
+    #[test]
+    fn interpolate_mean() {
+        let a = Se3::exp([0.1, -0.2, 0.3, 0.4, -0.5, 0.6]);
+        let b = Se3::exp([-1., 0.5, 0., 0., 0.2, -0.1]);
+        assert!(close(&a.interpolate(&b, 0.), &a, 1e-14));
+        assert!(close(&a.interpolate(&b, 1.), &b, 1e-12));
+        let m = a.interpolate(&b, 0.5);
+        assert!(close(
+            &Se3::mean(&[a.clone(), b.clone()]).unwrap(),
+            &m,
+            1e-12
+        ));
+        let d = |x: &Se3, y: &Se3| (&x.inverse() * y).log().iter().map(|x| x * x).sum::<f64>();
+        assert!((d(&a, &m) - d(&m, &b)).abs() < 1e-12);
+        assert!(Se3::mean(&[]).is_none());
+    }
+}
@@ -1,14 +1,21 @@
 //! Geometric transformation for the GMT segmented mirrors
 
-use std::marker::PhantomData;
+#[cfg(feature = "serde")]
+use alloc::{format, string::String, string::ToString};
+use core::marker::PhantomData;
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
 
-use crate::{Conic, Error, Gmt, Quaternion, Vector, M1, M2};
+use core::convert::TryFrom;
+
+use crate::{Aperture, Conic, Error, Gmt, Quaternion, SegmentId, Transform, Vector, M1, M2};
 
 /// GMT segmented mirror
 #[derive(Debug, Clone)]
 pub struct Segment<M: Gmt> {
     /// Segment # id
-    id: i32,
+    id: SegmentId,
     /// Mirror height
     height: f64,
     /// Radial inclination angle
@@ -22,104 +29,339 @@ pub struct Segment<M: Gmt> {
     mirror: PhantomData<M>,
 }
 
+/// Serialization representation of a [`Segment`]
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Segment", deny_unknown_fields)]
+struct SegmentRepr {
+    mirror: String,
+    id: i32,
+    height: f64,
+    beta: Option<f64>,
+    distance: Option<f64>,
+    clocking: Option<i32>,
+    conic: Conic,
+}
+#[cfg(feature = "serde")]
+impl<M: Gmt> serde::Serialize for Segment<M> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SegmentRepr {
+            mirror: M::NAME.to_string(),
+            id: self.id.into(),
+            height: self.height,
+            beta: self.beta,
+            distance: self.distance,
+            clocking: self.cloking,
+            conic: self.conic.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de, M: Gmt> serde::Deserialize<'de> for Segment<M> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error as _;
+        let repr = SegmentRepr::deserialize(deserializer)?;
+        if repr.mirror != M::NAME {
+            return Err(D::Error::custom(format!(
+                "expected a {} segment, found a {} segment",
+                M::NAME,
+                repr.mirror
+            )));
+        }
+        let id = SegmentId::try_from(repr.id).map_err(D::Error::custom)?;
+        let outer = id.is_outer();
+        if outer && (repr.beta.is_none() || repr.distance.is_none() || repr.clocking.is_none()) {
+            return Err(D::Error::custom(Error::Prescription(
+                "an outer segment requires beta, distance and clocking",
+            )));
+        }
+        if !outer && (repr.beta.is_some() || repr.distance.is_some() || repr.clocking.is_some()) {
+            return Err(D::Error::custom(Error::Prescription(
+                "the center segment has no beta, distance and clocking",
+            )));
+        }
+        let finite = |x: Option<f64>| x.into_iter().all(f64::is_finite);
+        if !(repr.height.is_finite() && finite(repr.beta) && finite(repr.distance)) {
+            return Err(D::Error::custom(Error::Prescription(
+                "the segment height, beta and distance must be finite",
+            )));
+        }
+        let conic = Conic::new(repr.conic.radius, repr.conic.constant).map_err(D::Error::custom)?;
+        Ok(Self {
+            id,
+            height: repr.height,
+            beta: repr.beta,
+            distance: repr.distance,
+            cloking: repr.clocking,
+            conic,
+            mirror: PhantomData,
+        })
+    }
+}
+
 /// Segment specialization traits
 pub trait SegmentTrait {
-    fn new(o: i32) -> Result<Self, Error>
+    /// Returns the segment with the 1-based `id`
+    fn new(id: i32) -> Result<Self, Error>
     where
         Self: Sized;
+    /// Returns the segment with the [`SegmentId`] `id`
+    ///
+    /// # Panics
+    /// The default implementation panics if [`SegmentTrait::new`] rejects a valid [`SegmentId`]
+    fn from_id(id: SegmentId) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new(id.into()).expect("a SegmentId is a valid segment id")
+    }
     fn rotation(&self) -> Option<Quaternion>;
+    /// Returns the segment clear [`Aperture`]
+    fn aperture(&self) -> Aperture;
 }
 impl<M: Gmt> Segment<M> {
+    /// Returns the segment # id
+    pub fn id(&self) -> i32 {
+        self.id.into()
+    }
+    /// Returns the segment [`SegmentId`]
+    pub fn segment_id(&self) -> SegmentId {
+        self.id
+    }
+    /// Returns the mirror [`Conic`] surface
+    pub fn conic(&self) -> &Conic {
+        &self.conic
+    }
+    /// Returns the mirror height, the height of the parent conic vertex in the OSS
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+    /// Returns the distance of an outer segment origin to the OSS z-axis, `None` for the center segment
+    pub fn distance(&self) -> Option<f64> {
+        self.distance
+    }
+    /// Returns the radial inclination angle of an outer segment \[degree\], `None` for the center segment
+    pub fn beta(&self) -> Option<f64> {
+        self.beta
+    }
+    /// Returns the segment with a new mirror height, distance to the origin, radial inclination angle and conic
+    pub(crate) fn reshape(
+        &self,
+        height: f64,
+        distance: Option<f64>,
+        beta: Option<f64>,
+        conic: Conic,
+    ) -> Self {
+        Self {
+            id: self.id,
+            height,
+            beta,
+            distance,
+            cloking: self.cloking,
+            conic,
+            mirror: PhantomData,
+        }
+    }
+    /// Returns the segment at the mirror height `height` on the parent conic with the radius of
+    /// curvature scaled by `1 + radius_strain`, an outer segment distance to the OSS z-axis is
+    /// scaled by `1 + distance_strain`
+    ///
+    /// An outer segment stays on the new parent conic and its radial inclination angle follows
+    /// the change of the conic slope
+    pub(crate) fn rescale(&self, height: f64, distance_strain: f64, radius_strain: f64) -> Self {
+        let nominal = &self.conic;
+        let conic = Conic {
+            radius: nominal.radius * (1. + radius_strain),
+            ..nominal.clone()
+        };
+        let distance = self.distance.map(|d| d * (1. + distance_strain));
+        let beta = self
+            .beta
+            .zip(self.distance)
+            .zip(distance)
+            .map(|((beta, d), dt)| {
+                beta + (conic.slope(dt).abs().atan() - nominal.slope(d).abs().atan()).to_degrees()
+            });
+        self.reshape(height, distance, beta, conic)
+    }
     /// Returns a [`Vector`] with the segment origin coordinates in the OSS
     pub fn translation(&self) -> Vector {
-        if self.id < 7 {
-            let o = self.cloking.unwrap() as f64;
-            let d = self.distance.unwrap();
+        if let (Some(o), Some(d)) = (self.cloking, self.distance) {
             let z = self.conic.height(d);
-            let (s, c) = (90. + o).to_radians().sin_cos();
+            let (s, c) = (90. + o as f64).to_radians().sin_cos();
             Vector::from([d * c, d * s, self.height + z])
         } else {
             Vector::from([0., 0., self.height])
         }
     }
 }
+impl<M: Gmt> Segment<M>
+where
+    Segment<M>: SegmentTrait + Clone,
+{
+    /// Returns the 3x3 rotation matrix from the segment coordinates to the OSS
+    pub fn rotation_matrix(&self) -> [[f64; 3]; 3] {
+        self.rotation()
+            .map_or([[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]], |q| {
+                q.to_rotation_matrix()
+            })
+    }
+    /// Returns the height of the segment surface at the segment coordinates `(x,y)`
+    ///
+    /// The height is the distance along the segment z-axis from the segment origin
+    /// to the mirror parent conic
+    pub fn surface(&self, x: f64, y: f64) -> f64 {
+        let n = Vector::<f64>::k().vtov(self.clone());
+        let mut z = 0f64;
+        for _ in 0..20 {
+            let p = Vector::from([x, y, z]).to(self.clone());
+            let rho = p[0].hypot(p[1]);
+            let f = p[2] - self.height - self.conic.height(rho);
+            let df = if rho > 0f64 {
+                n[2] - self.conic.slope(rho) * (p[0] * n[0] + p[1] * n[1]) / rho
+            } else {
+                n[2]
+            };
+            let dz = f / df;
+            z -= dz;
+            if dz.abs() < 1e-15 {
+                break;
+            }
+        }
+        z
+    }
+    /// Returns the height of the mirror parent conic at the OSS coordinates `(x,y)`
+    pub fn parent_surface(&self, x: f64, y: f64) -> f64 {
+        self.height + self.conic.height(x.hypot(y))
+    }
+}
 impl SegmentTrait for Segment<M1> {
     /// Returns [`M1`] [`Segment`] `id`
     fn new(id: i32) -> Result<Self, Error> {
-        match id {
-            7 => Ok(Self {
-                id: 7,
+        SegmentId::try_from(id).map(Self::from_id)
+    }
+    /// Returns [`M1`] [`Segment`] [`SegmentId`] `id`
+    fn from_id(id: SegmentId) -> Self {
+        if id.is_center() {
+            Self {
+                id,
                 height: 3.9,
                 beta: None,
                 distance: None,
                 cloking: None,
                 conic: Conic::m1(),
                 mirror: PhantomData,
-            }),
-            id @ 1..=6 => Ok(Self {
+            }
+        } else {
+            Self {
                 id,
                 height: 3.9,
                 beta: Some(13.601685f64),
                 distance: Some(8.71),
-                cloking: Some(-60i32 * (id - 1)),
+                cloking: Some(-60i32 * id.index() as i32),
                 conic: Conic::m1(),
                 mirror: PhantomData,
-            }),
-            _ => Err(Error::SegmentId(id)),
+            }
         }
     }
     /// Returns a [`Quaternion`] representing the 3D rotation of a [`M1`] [`Segment`] frame in the OSS
     fn rotation(&self) -> Option<Quaternion> {
-        if self.id < 7 {
-            let o = self.cloking.unwrap() as f64;
+        if let (Some(o), Some(beta)) = (self.cloking, self.beta) {
             Some(
-                Quaternion::unit(o.to_radians(), Vector::k())
-                    * Quaternion::unit(self.beta.unwrap().to_radians(), Vector::i()),
+                Quaternion::rotation((o as f64).to_radians(), &Vector::k())
+                    * Quaternion::rotation(beta.to_radians(), &Vector::i()),
             )
         } else {
             None
         }
     }
+    /// Returns the clear [`Aperture`] of a [`M1`] [`Segment`]
+    fn aperture(&self) -> Aperture {
+        Aperture {
+            outer_diameter: 8.365,
+            inner_diameter: if self.id.is_outer() { 0. } else { 3.2 },
+        }
+    }
 }
 impl SegmentTrait for Segment<M2> {
     /// Returns [`M2`] [`Segment`] `id`
     fn new(id: i32) -> Result<Self, Error> {
-        match id {
-            7 => Ok(Self {
-                id: 7,
+        SegmentId::try_from(id).map(Self::from_id)
+    }
+    /// Returns [`M2`] [`Segment`] [`SegmentId`] `id`
+    fn from_id(id: SegmentId) -> Self {
+        if id.is_center() {
+            Self {
+                id,
                 height: 3.9 + 20.26247614,
                 beta: None,
                 distance: None,
                 cloking: None,
                 conic: Conic::m2(),
                 mirror: PhantomData,
-            }),
-            id @ 1..=6 => Ok(Self {
+            }
+        } else {
+            Self {
                 id,
                 height: 3.9 + 20.26247614,
                 beta: Some(14.777498),
                 distance: Some(1.08774),
-                cloking: Some(180i32 - 60i32 * (id - 1)),
+                cloking: Some(180i32 - 60i32 * id.index() as i32),
                 conic: Conic::m2(),
                 mirror: PhantomData,
-            }),
-            _ => Err(Error::SegmentId(id)),
+            }
         }
     }
     /// Returns a [`Quaternion`] representing the 3D rotation of a [`M2`] [`Segment`] frame in the OSS
     fn rotation(&self) -> Option<Quaternion> {
-        if self.id < 7 {
-            let o = self.cloking.unwrap() as f64;
+        if let (Some(o), Some(beta)) = (self.cloking, self.beta) {
             Some(
-                Quaternion::unit(o.to_radians(), Vector::k())
-                    * Quaternion::unit(std::f64::consts::PI, Vector::j())
-                    * Quaternion::unit(self.beta.unwrap().to_radians(), Vector::i()),
+                Quaternion::rotation((o as f64).to_radians(), &Vector::k())
+                    * Quaternion::rotation(core::f64::consts::PI, &Vector::j())
+                    * Quaternion::rotation(beta.to_radians(), &Vector::i()),
             )
         } else {
             Some(
-                Quaternion::unit(180f64.to_radians(), Vector::k())
-                    * Quaternion::unit(std::f64::consts::PI, Vector::j()),
+                Quaternion::rotation(180f64.to_radians(), &Vector::k())
+                    * Quaternion::rotation(core::f64::consts::PI, &Vector::j()),
             )
         }
     }
+    /// Returns the clear [`Aperture`] of a [`M2`] [`Segment`]
+    fn aperture(&self) -> Aperture {
+        Aperture {
+            outer_diameter: 1.05,
+            inner_diameter: 0.,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_roundtrip() {
+        for sid in 1..=7 {
+            let segment = Segment::<M2>::new(sid).unwrap();
+            let json = serde_json::to_string(&segment).unwrap();
+            let other: Segment<M2> = serde_json::from_str(&json).unwrap();
+            assert_eq!(other.translation(), segment.translation());
+            assert_eq!(other.rotation(), segment.rotation());
+            assert_eq!(serde_json::to_string(&other).unwrap(), json);
+            assert!(serde_json::from_str::<Segment<M1>>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn serde_format() {
+        let json = serde_json::to_string(&Segment::<M1>::new(7).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"mirror":"M1","id":7,"height":3.9,"beta":null,"distance":null,"clocking":null,"conic":{"radius":36.0,"constant":-0.9982857}}"#
+        );
+        let json = json.replace(r#""id":7"#, r#""id":1"#);
+        assert!(serde_json::from_str::<Segment<M1>>(&json).is_err());
+    }
 }
@@ -0,0 +1,157 @@
+//! Segment ids
+//!
+//! The segments are numbered from 1 to 7, the outer segments 1 to 6 and the center segment 7.
+//! [`SegmentId`] converts explicitly between the 1-based ids ([`SegmentId::new`], [`SegmentId::get`])
+//! and the 0-based array indices ([`SegmentId::from_index`], [`SegmentId::index`]).
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::{Error, Gmt, Mirror, Segment, SegmentTrait};
+
+/// Segment # id in the range \[1,7\]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentId(u8);
+impl SegmentId {
+    /// Number of segments
+    pub const N: usize = 7;
+    /// Center segment id
+    pub const CENTER: SegmentId = SegmentId(7);
+    /// Creates a segment id from the 1-based `id`, `None` if `id` is not in the range \[1,7\]
+    pub const fn new(id: u8) -> Option<Self> {
+        if id >= 1 && id <= 7 {
+            Some(Self(id))
+        } else {
+            None
+        }
+    }
+    /// Creates a segment id from the 0-based `index`, `None` if `index` is not in the range \[0,6\]
+    pub const fn from_index(index: usize) -> Option<Self> {
+        if index < Self::N {
+            Some(Self(index as u8 + 1))
+        } else {
+            None
+        }
+    }
+    /// Returns the 1-based id
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+    /// Returns the 0-based index
+    pub const fn index(self) -> usize {
+        self.0 as usize - 1
+    }
+    /// Checks if this is the center segment
+    pub const fn is_center(self) -> bool {
+        self.0 == 7
+    }
+    /// Checks if this is an outer segment
+    pub const fn is_outer(self) -> bool {
+        self.0 < 7
+    }
+    /// Iterates over the 7 segment ids
+    pub fn all() -> impl Iterator<Item = SegmentId> + Clone {
+        (1..=7).map(Self)
+    }
+    /// Iterates over the 6 outer segment ids
+    pub fn outer() -> impl Iterator<Item = SegmentId> + Clone {
+        (1..=6).map(Self)
+    }
+}
+impl TryFrom<u8> for SegmentId {
+    type Error = Error;
+    /// Converts the 1-based `id`
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        Self::new(id).ok_or(Error::SegmentId(id as i32))
+    }
+}
+impl TryFrom<i32> for SegmentId {
+    type Error = Error;
+    /// Converts the 1-based `id`
+    fn try_from(id: i32) -> Result<Self, Self::Error> {
+        u8::try_from(id)
+            .ok()
+            .and_then(Self::new)
+            .ok_or(Error::SegmentId(id))
+    }
+}
+impl TryFrom<usize> for SegmentId {
+    type Error = Error;
+    /// Converts the 1-based `id`, use [`SegmentId::from_index`] for 0-based indices
+    fn try_from(id: usize) -> Result<Self, Self::Error> {
+        u8::try_from(id)
+            .ok()
+            .and_then(Self::new)
+            .ok_or_else(|| Error::SegmentId(i32::try_from(id).unwrap_or(i32::MAX)))
+    }
+}
+impl From<SegmentId> for u8 {
+    fn from(id: SegmentId) -> Self {
+        id.0
+    }
+}
+impl From<SegmentId> for i32 {
+    fn from(id: SegmentId) -> Self {
+        id.0 as i32
+    }
+}
+impl fmt::Display for SegmentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait,
+{
+    /// Iterates over the 7 segments
+    pub fn segments() -> impl Iterator<Item = Segment<M>> {
+        SegmentId::all().map(Segment::<M>::from_id)
+    }
+    /// Iterates over the 6 outer segments
+    pub fn outer_segments() -> impl Iterator<Item = Segment<M>> {
+        SegmentId::outer().map(Segment::<M>::from_id)
+    }
+    /// Returns the center segment
+    pub fn center_segment() -> Segment<M> {
+        Segment::<M>::from_id(SegmentId::CENTER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{M1, M2};
+
+    #[test]
+    fn conversions() {
+        const S3: Option<SegmentId> = SegmentId::new(3);
+        let id = S3.unwrap();
+        assert_eq!((id.get(), id.index()), (3, 2));
+        assert_eq!(SegmentId::from_index(2), Some(id));
+        assert_eq!(SegmentId::try_from(3usize).unwrap(), id);
+        assert_eq!(i32::from(id), 3);
+        assert!(SegmentId::new(0).is_none() && SegmentId::from_index(7).is_none());
+        assert!(matches!(
+            SegmentId::try_from(8i32),
+            Err(Error::SegmentId(8))
+        ));
+        assert!(matches!(
+            SegmentId::try_from(-1i32),
+            Err(Error::SegmentId(-1))
+        ));
+        assert!(SegmentId::try_from(0u8).is_err());
+    }
+
+    #[test]
+    fn iterators() {
+        assert_eq!(SegmentId::all().count(), SegmentId::N);
+        assert!(SegmentId::outer().all(|id| id.is_outer()));
+        assert!(Mirror::<M1>::segments()
+            .zip(1..=7)
+            .all(|(segment, sid)| segment.id() == sid));
+        assert_eq!(Mirror::<M2>::outer_segments().count(), 6);
+        assert!(Mirror::<M2>::center_segment().segment_id().is_center());
+    }
+}
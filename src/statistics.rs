@@ -0,0 +1,58 @@
+//! Summary statistics
+//!
+//! The [`Summary`] of a set of samples is shared by the Monte Carlo runs and the rigid body motions time series.
+
+/// Summary statistics of a set of samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    /// Mean
+    pub mean: f64,
+    /// Standard deviation
+    pub std: f64,
+    /// Minimum
+    pub min: f64,
+    /// Maximum
+    pub max: f64,
+}
+impl Summary {
+    /// Computes the statistics of the `samples`, all NaN if there are no samples
+    pub fn new(samples: &[f64]) -> Self {
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let var = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        let (min, max) = if samples.is_empty() {
+            (f64::NAN, f64::NAN)
+        } else {
+            samples
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), &x| {
+                    (a.min(x), b.max(x))
+                })
+        };
+        Self {
+            mean,
+            std: var.sqrt(),
+            min,
+            max,
+        }
+    }
+    /// Returns the root mean square of the samples
+    pub fn rms(&self) -> f64 {
+        self.mean.hypot(self.std)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let s = Summary::new(&[1., -1., 3., 1.]);
+        assert_eq!((s.mean, s.min, s.max), (1., -1., 3.));
+        assert!((s.std - 2f64.sqrt()).abs() < 1e-15);
+        assert!((s.rms() - 3f64.sqrt()).abs() < 1e-15);
+        let s = Summary::new(&[]);
+        assert!(s.mean.is_nan() && s.std.is_nan() && s.min.is_nan() && s.max.is_nan());
+    }
+}
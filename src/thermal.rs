@@ -0,0 +1,176 @@
+//! Thermal expansion of the segment geometry
+//!
+//! The segment geometry is given at the nominal temperature.
+//! At a temperature change `ΔT`, each structure expands by the strain `α ΔT` with `α` its
+//! coefficient of thermal expansion (CTE):
+//!  * the mirror cells scale the M1 height and the distances of the outer segments to the OSS z-axis,
+//!  * the mirror glass scales the radius of curvature of the conics,
+//!  * the truss scales the M1–M2 spacing.
+//!
+//! The outer segments remain on the parent conic and their radial inclination angles follow
+//! the change of the conic slope.
+//! The changes of the segment positions and orientations are returned as the equivalent
+//! rigid body motions of the nominal segments with [`Mirror::thermal_rigidbodymotions`].
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::{Gmt, Mirror, Rbm};
+use crate::{Segment, SegmentId, SegmentTrait, M1, M2};
+
+/// Coefficients of thermal expansion \[1/K\]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cte {
+    /// Mirror glass
+    pub glass: f64,
+    /// Mirror cells
+    pub cell: f64,
+    /// Truss between the M1 and M2 cells
+    pub truss: f64,
+}
+impl Cte {
+    /// Creates the coefficients of thermal expansion of the mirror glass, cells and truss
+    pub fn new(glass: f64, cell: f64, truss: f64) -> Self {
+        Self { glass, cell, truss }
+    }
+    /// Creates the same coefficient of thermal expansion for all the structures
+    pub fn uniform(cte: f64) -> Self {
+        Self::new(cte, cte, cte)
+    }
+}
+
+/// Temperature change with respect to the nominal temperature
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Expansion {
+    /// Temperature change \[K\]
+    pub temperature: f64,
+    /// Coefficients of thermal expansion
+    pub cte: Cte,
+}
+impl Expansion {
+    /// Creates a new thermal expansion for the temperature change `temperature` \[K\]
+    pub fn new(temperature: f64, cte: Cte) -> Self {
+        Self { temperature, cte }
+    }
+    /// Creates a new thermal expansion with the same coefficient of thermal expansion for all the structures
+    pub fn uniform(temperature: f64, cte: f64) -> Self {
+        Self::new(temperature, Cte::uniform(cte))
+    }
+    /// Returns the strains `(glass, cell, truss)`
+    pub fn strains(&self) -> (f64, f64, f64) {
+        let Cte { glass, cell, truss } = self.cte;
+        let t = self.temperature;
+        (glass * t, cell * t, truss * t)
+    }
+    /// Returns the change of the M1–M2 spacing \[m\]
+    pub fn spacing(&self) -> f64 {
+        let (_, _, truss) = self.strains();
+        spacing() * truss
+    }
+}
+
+/// Returns the nominal M1–M2 spacing
+fn spacing() -> f64 {
+    Segment::<M2>::from_id(SegmentId::CENTER).height()
+        - Segment::<M1>::from_id(SegmentId::CENTER).height()
+}
+
+/// Thermal expansion of the segment geometry
+pub trait ThermalExpansion {
+    /// Returns the segment geometry for the thermal `expansion`
+    fn expand(&self, expansion: &Expansion) -> Self;
+}
+impl ThermalExpansion for Segment<M1> {
+    /// Expands a [`M1`] [`Segment`], the M1 height scales with the cell
+    fn expand(&self, expansion: &Expansion) -> Self {
+        let (glass, cell, _) = expansion.strains();
+        self.rescale(self.height() * (1. + cell), cell, glass)
+    }
+}
+impl ThermalExpansion for Segment<M2> {
+    /// Expands a [`M2`] [`Segment`], the M2 height is the expanded M1 height plus the expanded M1–M2 spacing
+    fn expand(&self, expansion: &Expansion) -> Self {
+        let (glass, cell, _) = expansion.strains();
+        let m1 = Segment::<M1>::from_id(SegmentId::CENTER).expand(expansion);
+        let height = m1.height() + spacing() + expansion.spacing();
+        self.rescale(height, cell, glass)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<M: Gmt> Mirror<M>
+where
+    Segment<M>: SegmentTrait + ThermalExpansion + Clone,
+{
+    /// Returns the 7 segment rigid body motions that move the nominal segments onto the
+    /// segments of the thermal `expansion`
+    pub fn thermal_rigidbodymotions(expansion: &Expansion) -> Vec<Rbm> {
+        Self::reshape_2_rigidbodymotions(|segment| segment.expand(expansion))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::{Transform, Vector};
+
+    #[test]
+    fn nominal() {
+        let expansion = Expansion::uniform(0., 1e-5);
+        for rbm in Mirror::<M2>::thermal_rigidbodymotions(&expansion) {
+            assert!(<[f64; 6]>::from(rbm).iter().all(|x| x.abs() < 1e-15));
+        }
+        let segment = Segment::<M1>::new(3).unwrap();
+        let expanded = segment.expand(&expansion);
+        assert_eq!(expanded.translation(), segment.translation());
+        assert_eq!(expanded.rotation(), segment.rotation());
+    }
+
+    #[test]
+    fn uniform() {
+        let expansion = Expansion::uniform(-10., 2e-6);
+        let e = -2e-5;
+        for segment in Mirror::<M1>::segments() {
+            let expanded = segment.expand(&expansion);
+            // homothety about the OSS origin
+            let d = expanded.translation() - segment.translation();
+            let t = segment.translation();
+            assert!((d - &t * e).norm() < 1e-12, "{}", segment.id());
+            assert!(
+                (expanded.beta().unwrap_or_default() - segment.beta().unwrap_or_default()).abs()
+                    < 1e-12
+            );
+        }
+        assert!((expansion.spacing() - spacing() * e).abs() < 1e-15);
+        let rbm = Mirror::<M2>::thermal_rigidbodymotions(&expansion);
+        let dz = Vector::from(rbm[6].translation).vtov(Mirror::<M2>::center_segment());
+        assert!((dz[2] - Segment::<M2>::from_id(SegmentId::CENTER).height() * e).abs() < 1e-12);
+    }
+
+    #[test]
+    fn equivalent_rbm() {
+        let expansion = Expansion::new(5., Cte::new(3e-6, 12e-6, 1e-6));
+        let v = Vector::from([0.3, -0.4, 0.01]);
+        for (segment, rbm) in
+            Mirror::<M1>::segments().zip(Mirror::<M1>::thermal_rigidbodymotions(&expansion))
+        {
+            let expanded = segment.expand(&expansion);
+            let u = rbm.apply(v.clone()).to(segment.clone());
+            assert!((u - v.clone().to(expanded.clone())).norm() < 1e-12);
+            // the expanded segment origin is on the expanded parent conic
+            let o = expanded.translation();
+            assert!((o[2] - expanded.parent_surface(o[0], o[1])).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn glass() {
+        // the glass expansion changes the conic radius and the outer segment inclinations only
+        let expansion = Expansion::new(1., Cte::new(1e-5, 0., 0.));
+        let rbm = Mirror::<M1>::thermal_rigidbodymotions(&expansion);
+        assert!(<[f64; 6]>::from(rbm[6]).iter().all(|x| x.abs() < 1e-15));
+        assert!(rbm[..6].iter().all(|rbm| rbm.rotation[0].abs() > 1e-7));
+        assert_eq!(expansion.spacing(), 0.);
+    }
+}
@@ -0,0 +1,358 @@
+//! Time series of segment rigid body motions
+//!
+//! A [`RbmSeries`] holds the rigid body motions of the same segments, e.g. the 7 segments of a mirror
+//! or the 14 segments of M1 and M2, at increasing time stamps.
+//! The degrees of freedom (DOF) of a sample are ordered as the 42 or 84 elements rigid body motions
+//! vectors: `[Tx,Ty,Tz,Rx,Ry,Rz]` of the first segment, then of the second segment, etc.
+
+use std::convert::TryFrom;
+
+use crate::{statistics::Summary, Error, Quaternion, Rbm};
+
+/// Interpolation of the rigid body motions between 2 samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Linear interpolation of the translations and of the Euler angles
+    Linear,
+    /// Linear interpolation of the translations and spherical linear interpolation of the rotations
+    Slerp,
+}
+impl Interpolation {
+    /// Interpolates between `a` (`s=0`) and `b` (`s=1`)
+    fn apply(self, a: &Rbm, b: &Rbm, s: f64) -> Rbm {
+        let lerp = |x: [f64; 3], y: [f64; 3]| {
+            let mut z = x;
+            z.iter_mut().zip(&y).for_each(|(z, y)| *z += s * (y - *z));
+            z
+        };
+        let translation = lerp(a.translation, b.translation);
+        match self {
+            Interpolation::Linear => Rbm::new(translation, lerp(a.rotation, b.rotation)),
+            Interpolation::Slerp => {
+                let (r, p, y) = a.quaternion().slerp(&b.quaternion(), s).euler_angles();
+                Rbm::new(translation, [r, p, y])
+            }
+        }
+    }
+}
+
+/// Returns the normalized sum of the rotation quaternions, with the signs of the quaternions
+/// aligned on the first one
+fn mean_rotation<I: Iterator<Item = Quaternion>>(mut quaternions: I) -> Quaternion {
+    let Some(q0) = quaternions.next() else {
+        return Quaternion::identity();
+    };
+    let sum = quaternions.fold(q0.clone(), |sum, q| {
+        if q0.scalar() * q.scalar() + q0.vector().dot(&q.vector()) < 0. {
+            sum - q
+        } else {
+            sum + q
+        }
+    });
+    let norm = sum.norm();
+    sum / norm
+}
+
+/// Time series of segment rigid body motions
+#[derive(Debug, Clone, PartialEq)]
+pub struct RbmSeries {
+    time: Vec<f64>,
+    samples: Vec<Vec<Rbm>>,
+}
+impl RbmSeries {
+    /// Creates a new time series from the time stamps \[s\] and the segment rigid body motions
+    ///
+    /// The time stamps must be finite and strictly increasing and all the samples must have
+    /// the same number of segments
+    pub fn new(time: Vec<f64>, samples: Vec<Vec<Rbm>>) -> Result<Self, Error> {
+        if time.is_empty() || time.len() != samples.len() {
+            return Err(Error::TimeSeries(
+                "the numbers of time stamps and of samples must be equal and non-zero",
+            ));
+        }
+        if !(time.iter().all(|t| t.is_finite()) && time.windows(2).all(|t| t[0] < t[1])) {
+            return Err(Error::TimeSeries(
+                "the time stamps must be finite and strictly increasing",
+            ));
+        }
+        let n = samples[0].len();
+        if n == 0 || samples.iter().any(|sample| sample.len() != n) {
+            return Err(Error::TimeSeries(
+                "the samples must have the same non-zero number of segments",
+            ));
+        }
+        Ok(Self { time, samples })
+    }
+    /// Creates a new time series from the time stamps \[s\] and the rigid body motions vectors
+    ///
+    /// The vectors length must be a multiple of 6, e.g. 42 for a mirror or 84 for M1 and M2
+    pub fn from_vectors(time: Vec<f64>, vectors: &[Vec<f64>]) -> Result<Self, Error> {
+        let samples = vectors
+            .iter()
+            .map(|v| {
+                if v.len() % 6 == 0 {
+                    v.chunks(6).map(Rbm::try_from).collect()
+                } else {
+                    Err(Error::TimeSeries(
+                        "the rigid body motions vectors length must be a multiple of 6",
+                    ))
+                }
+            })
+            .collect::<Result<Vec<Vec<Rbm>>, Error>>()?;
+        Self::new(time, samples)
+    }
+    /// Returns the rigid body motions vectors
+    pub fn to_vectors(&self) -> Vec<Vec<f64>> {
+        self.samples
+            .iter()
+            .map(|rbms| Rbm::to_mirror(rbms))
+            .collect()
+    }
+    /// Returns the number of samples
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+    /// Checks if the time series is empty, always false
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+    /// Returns the number of segments
+    pub fn n_segment(&self) -> usize {
+        self.samples[0].len()
+    }
+    /// Returns the time stamps \[s\]
+    pub fn time(&self) -> &[f64] {
+        &self.time
+    }
+    /// Returns the segment rigid body motions samples
+    pub fn samples(&self) -> &[Vec<Rbm>] {
+        &self.samples
+    }
+    /// Returns the time series of the degree of freedom `dof`
+    pub fn dof(&self, dof: usize) -> Option<Vec<f64>> {
+        (dof < 6 * self.n_segment()).then(|| {
+            self.samples
+                .iter()
+                .map(|rbms| <[f64; 6]>::from(rbms[dof / 6])[dof % 6])
+                .collect()
+        })
+    }
+    /// Returns the segment rigid body motions at the time `t`, `None` if `t` is outside the time series
+    pub fn interpolate(&self, t: f64, interpolation: Interpolation) -> Option<Vec<Rbm>> {
+        let (first, last) = (self.time[0], self.time[self.len() - 1]);
+        if !(first..=last).contains(&t) {
+            return None;
+        }
+        let i = self.time.partition_point(|&x| x <= t);
+        if i == self.len() {
+            return Some(self.samples[i - 1].clone());
+        }
+        let s = (t - self.time[i - 1]) / (self.time[i] - self.time[i - 1]);
+        Some(
+            self.samples[i - 1]
+                .iter()
+                .zip(&self.samples[i])
+                .map(|(a, b)| interpolation.apply(a, b, s))
+                .collect(),
+        )
+    }
+    /// Samples the time series at the time stamps `time`
+    ///
+    /// The time stamps must be strictly increasing and within the time series
+    pub fn sample(&self, time: &[f64], interpolation: Interpolation) -> Result<Self, Error> {
+        let samples = time
+            .iter()
+            .map(|&t| self.interpolate(t, interpolation))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::TimeSeries(
+                "the time stamps are outside of the time series",
+            ))?;
+        Self::new(time.to_vec(), samples)
+    }
+    /// Resamples the time series at the sampling rate `rate` \[Hz\] from the first time stamp
+    pub fn resample(&self, rate: f64, interpolation: Interpolation) -> Result<Self, Error> {
+        if !(rate.is_finite() && rate > 0.) {
+            return Err(Error::TimeSeries("the sampling rate must be positive"));
+        }
+        let (first, last) = (self.time[0], self.time[self.len() - 1]);
+        let n = ((last - first) * rate * (1. + f64::EPSILON)).floor() as usize + 1;
+        let time: Vec<f64> = (0..n)
+            .map(|i| (first + i as f64 / rate).min(last))
+            .collect();
+        self.sample(&time, interpolation)
+    }
+    /// Decimates the time series by averaging the blocks of `factor` consecutive samples
+    ///
+    /// The rotations of a block are averaged with the normalized sum of the rotation quaternions
+    /// and a last incomplete block is averaged over its samples
+    pub fn decimate(&self, factor: usize) -> Result<Self, Error> {
+        if factor == 0 {
+            return Err(Error::TimeSeries("the decimation factor must be positive"));
+        }
+        let time = self
+            .time
+            .chunks(factor)
+            .map(|t| t.iter().sum::<f64>() / t.len() as f64)
+            .collect();
+        let samples = self
+            .samples
+            .chunks(factor)
+            .map(|block| {
+                let n = block.len() as f64;
+                (0..self.n_segment())
+                    .map(|k| {
+                        let mut translation = [0f64; 3];
+                        for rbms in block {
+                            translation
+                                .iter_mut()
+                                .zip(&rbms[k].translation)
+                                .for_each(|(t, x)| *t += x / n);
+                        }
+                        let (r, p, y) =
+                            mean_rotation(block.iter().map(|rbms| rbms[k].quaternion()))
+                                .euler_angles();
+                        Rbm::new(translation, [r, p, y])
+                    })
+                    .collect()
+            })
+            .collect();
+        Self::new(time, samples)
+    }
+    /// Returns the statistics of each degree of freedom
+    pub fn statistics(&self) -> Vec<Summary> {
+        (0..6 * self.n_segment())
+            .filter_map(|dof| self.dof(dof))
+            .map(|x| Summary::new(&x))
+            .collect()
+    }
+    /// Removes the least-squares linear trend in time from each degree of freedom
+    ///
+    /// The rotations are detrended as Euler angles, for small rotations
+    pub fn detrend(&self) -> Self {
+        let n = self.len() as f64;
+        let t_mean = self.time.iter().sum::<f64>() / n;
+        let t_var: f64 = self.time.iter().map(|t| (t - t_mean).powi(2)).sum();
+        let mut vectors = self.to_vectors();
+        for dof in 0..6 * self.n_segment() {
+            let x: Vec<f64> = vectors.iter().map(|v| v[dof]).collect();
+            let x_mean = x.iter().sum::<f64>() / n;
+            let slope = if t_var > 0. {
+                self.time
+                    .iter()
+                    .zip(&x)
+                    .map(|(t, x)| (t - t_mean) * (x - x_mean))
+                    .sum::<f64>()
+                    / t_var
+            } else {
+                0.
+            };
+            vectors
+                .iter_mut()
+                .zip(&self.time)
+                .for_each(|(v, t)| v[dof] -= x_mean + slope * (t - t_mean));
+        }
+        Self {
+            time: self.time.clone(),
+            samples: vectors
+                .iter()
+                .map(|v| {
+                    v.chunks(6)
+                        .map(|x| Rbm::new([x[0], x[1], x[2]], [x[3], x[4], x[5]]))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp(n: usize) -> RbmSeries {
+        let time: Vec<f64> = (0..n).map(|i| i as f64 * 0.1).collect();
+        let vectors: Vec<Vec<f64>> = time
+            .iter()
+            .map(|t| (0..42).map(|k| (k + 1) as f64 * 1e-6 * t).collect())
+            .collect();
+        RbmSeries::from_vectors(time, &vectors).unwrap()
+    }
+
+    #[test]
+    fn new() {
+        let series = ramp(5);
+        assert_eq!((series.len(), series.n_segment()), (5, 7));
+        assert!(RbmSeries::from_vectors(vec![0.], &[vec![0.; 43]]).is_err());
+        assert!(RbmSeries::new(vec![0., 0.], vec![vec![Rbm::default()]; 2]).is_err());
+        assert!(RbmSeries::new(vec![0., 1.], vec![vec![Rbm::default()]; 3]).is_err());
+        let m12 = RbmSeries::from_vectors(vec![0.], &[vec![0.; 84]]).unwrap();
+        assert_eq!(m12.n_segment(), 14);
+        assert!(matches!(
+            RbmSeries::new(
+                vec![0., 1.],
+                vec![vec![Rbm::default(); 7], vec![Rbm::default(); 14]]
+            ),
+            Err(Error::TimeSeries(_))
+        ));
+    }
+
+    #[test]
+    fn interpolate() {
+        let series = ramp(5);
+        // the Euler angles of the spherical interpolation differ to the second order
+        for interpolation in [Interpolation::Linear, Interpolation::Slerp] {
+            let rbm = Rbm::to_mirror(&series.interpolate(0.25, interpolation).unwrap());
+            assert!(rbm
+                .iter()
+                .enumerate()
+                .all(|(k, x)| (x - (k + 1) as f64 * 0.25e-6).abs() < 1e-11));
+        }
+        assert!(series.interpolate(0.41, Interpolation::Linear).is_none());
+        let last = series.interpolate(0.4, Interpolation::Linear).unwrap();
+        assert_eq!(last, series.samples()[4]);
+    }
+
+    #[test]
+    fn slerp() {
+        let time = vec![0., 1.];
+        let a = Rbm::new([0.; 3], [0., 0., 0.2]);
+        let b = Rbm::new([1., 0., 0.], [0., 0., 1.2]);
+        let series = RbmSeries::new(time, vec![vec![a], vec![b]]).unwrap();
+        let rbm = series.interpolate(0.3, Interpolation::Slerp).unwrap()[0];
+        assert!((rbm.rotation[2] - 0.5).abs() < 1e-15 && (rbm.translation[0] - 0.3).abs() < 1e-15);
+    }
+
+    #[test]
+    fn resample_decimate() {
+        let series = ramp(5);
+        let resampled = series.resample(20., Interpolation::Slerp).unwrap();
+        assert_eq!(resampled.len(), 9);
+        let decimated = resampled.decimate(2).unwrap();
+        assert_eq!(decimated.len(), 5);
+        assert!((decimated.time()[1] - 0.125).abs() < 1e-15);
+        let rbm = Rbm::to_mirror(&decimated.samples()[1]);
+        assert!(rbm
+            .iter()
+            .enumerate()
+            .all(|(k, x)| (x - (k + 1) as f64 * 0.125e-6).abs() < 1e-11));
+        assert!(
+            series.decimate(0).is_err() && series.resample(-1., Interpolation::Linear).is_err()
+        );
+    }
+
+    #[test]
+    fn statistics_detrend() {
+        let series = ramp(5);
+        let stats = series.statistics();
+        assert_eq!(stats.len(), 42);
+        assert!((stats[0].mean - 0.2e-6).abs() < 1e-18);
+        assert!((stats[0].rms() - (0.06f64).sqrt() * 1e-6).abs() < 1e-18);
+        let detrended = series.detrend();
+        assert!(detrended
+            .to_vectors()
+            .iter()
+            .flatten()
+            .all(|x| x.abs() < 1e-18));
+        assert_eq!(series.dof(42), None);
+    }
+}
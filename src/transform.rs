@@ -1,149 +1,188 @@
-use crate::{Gmt, Quaternion, Segment, SegmentTrait, Vector};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::{Error, Gmt, Real, Segment, SegmentTrait, Vector};
 
 /// Geometric transformation with respect to the OSS coordinate system
-pub trait Transform {
+///
+/// The transformations are computed with the floating point type `T`.
+/// The transforms apply to the values that convert into a [`Vector`] (`[T; 3]` and [`Vector`]),
+/// the `try_` transforms to the values that may fail to convert (`Vec<T>`) and return the conversion error.
+pub trait Transform<T: Real = f64> {
     /// Transforms the coordinates given in the OSS into a segment
     fn fro<M>(self, segment: Segment<M>) -> Self
     where
         M: Gmt,
-        Self: Into<Vector>,
-        Vector: Into<Self>,
+        Self: Into<Vector<T>>,
+        Vector<T>: Into<Self>,
         Segment<M>: SegmentTrait,
     {
-        let u: Vector = self.into();
-        let t = segment.translation();
-        if let Some(q) = segment.rotation() {
-            // let p: Quaternion = From::<Vector>::from(u);
-            let v = Vector::from((q.complex_conjugate() * (u - t) * &q).vector_as_slice());
-            v.into()
-        } else {
-            (u - t).into()
-        }
+        let pose = segment.pose().cast::<T>().inverse();
+        pose.transform(self.into()).into()
     }
     /// Transforms a the vector given in the OSS into a segment
     fn vfrov<M>(self, segment: Segment<M>) -> Self
     where
         M: Gmt,
-        Self: Into<Vector>,
-        Vector: Into<Self>,
+        Self: Into<Vector<T>>,
+        Vector<T>: Into<Self>,
         Segment<M>: SegmentTrait,
     {
-        if let Some(q) = segment.rotation() {
-            let u: Vector = self.into();
-            let p: Quaternion = From::<Vector>::from(u);
-            let v = Vector::from((q.complex_conjugate() * p * &q).vector_as_slice());
-            v.into()
-        } else {
-            self
-        }
+        let pose = segment.pose().cast::<T>().inverse();
+        pose.rotate(self.into()).into()
     }
     /// Transforms the coordinates of a segment into the OSS
     fn to<M>(self, segment: Segment<M>) -> Self
     where
         M: Gmt,
-        Self: Into<Vector>,
-        Vector: Into<Self>,
+        Self: Into<Vector<T>>,
+        Vector<T>: Into<Self>,
         Segment<M>: SegmentTrait,
     {
-        let u: Vector = self.into();
-        let t = segment.translation();
-        if let Some(q) = segment.rotation() {
-            let p: Quaternion = From::<Vector>::from(u);
-            let v = Vector::from(
-                (&q * p * q.complex_conjugate() + From::<Vector>::from(t)).vector_as_slice(),
-            );
-            v.into()
-        } else {
-            (u + t).into()
-        }
+        let pose = segment.pose().cast::<T>();
+        pose.transform(self.into()).into()
     }
     /// Transforms a vector of a segment into the OSS
     fn vtov<M>(self, segment: Segment<M>) -> Self
     where
         M: Gmt,
-        Self: Into<Vector>,
-        Vector: Into<Self>,
+        Self: Into<Vector<T>>,
+        Vector<T>: Into<Self>,
+        Segment<M>: SegmentTrait,
+    {
+        let pose = segment.pose().cast::<T>();
+        pose.rotate(self.into()).into()
+    }
+    /// Transforms the coordinates given in the OSS into a segment
+    ///
+    /// Returns an error if `self` cannot be converted into a [`Vector`]
+    fn try_fro<M>(self, segment: Segment<M>) -> Result<Self, Error>
+    where
+        M: Gmt,
+        Self: TryInto<Vector<T>>,
+        Error: From<<Self as TryInto<Vector<T>>>::Error>,
+        Vector<T>: Into<Self>,
+        Segment<M>: SegmentTrait,
+    {
+        let pose = segment.pose().cast::<T>().inverse();
+        Ok(pose.transform(self.try_into()?).into())
+    }
+    /// Transforms a the vector given in the OSS into a segment
+    ///
+    /// Returns an error if `self` cannot be converted into a [`Vector`]
+    fn try_vfrov<M>(self, segment: Segment<M>) -> Result<Self, Error>
+    where
+        M: Gmt,
+        Self: TryInto<Vector<T>>,
+        Error: From<<Self as TryInto<Vector<T>>>::Error>,
+        Vector<T>: Into<Self>,
+        Segment<M>: SegmentTrait,
+    {
+        let pose = segment.pose().cast::<T>().inverse();
+        Ok(pose.rotate(self.try_into()?).into())
+    }
+    /// Transforms the coordinates of a segment into the OSS
+    ///
+    /// Returns an error if `self` cannot be converted into a [`Vector`]
+    fn try_to<M>(self, segment: Segment<M>) -> Result<Self, Error>
+    where
+        M: Gmt,
+        Self: TryInto<Vector<T>>,
+        Error: From<<Self as TryInto<Vector<T>>>::Error>,
+        Vector<T>: Into<Self>,
+        Segment<M>: SegmentTrait,
+    {
+        let pose = segment.pose().cast::<T>();
+        Ok(pose.transform(self.try_into()?).into())
+    }
+    /// Transforms a vector of a segment into the OSS
+    ///
+    /// Returns an error if `self` cannot be converted into a [`Vector`]
+    fn try_vtov<M>(self, segment: Segment<M>) -> Result<Self, Error>
+    where
+        M: Gmt,
+        Self: TryInto<Vector<T>>,
+        Error: From<<Self as TryInto<Vector<T>>>::Error>,
+        Vector<T>: Into<Self>,
         Segment<M>: SegmentTrait,
     {
-        if let Some(q) = segment.rotation() {
-            let u: Vector = self.into();
-            let p: Quaternion = From::<Vector>::from(u);
-            let v = Vector::from((&q * p * q.complex_conjugate()).vector_as_slice());
-            v.into()
-        } else {
-            self
-        }
+        let pose = segment.pose().cast::<T>();
+        Ok(pose.rotate(self.try_into()?).into())
     }
 }
-impl Transform for [f64; 3] {}
-impl Transform for Vec<f64> {}
-impl Transform for Vector {}
+impl<T: Real> Transform<T> for [T; 3] {}
+impl<T: Real> Transform<T> for Vector<T> {}
+/// Only the `try_` transforms apply to a vector, they fail if its length is neither 2 nor 3
+/// or if it has non-finite values
+#[cfg(feature = "alloc")]
+impl<T: Real> Transform<T> for Vec<T> {}
 /// Mutable geometric transformation with respect to the OSS coordinate system
-pub trait TransformMut<'a> {
+pub trait TransformMut<'a, T: Real = f64> {
     /// Transforms the coordinates given in the OSS into a segment
     fn fro<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait;
     /// Transforms a vector coordinates given in the OSS into a segment
     fn vfrov<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait;
     /// Transforms the coordinates of a segment into the OSS
     fn to<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait;
     /// Transforms a segment of a segment into the OSS
     fn vtov<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait;
 }
-impl<'a> TransformMut<'a> for &'a mut [f64; 3] {
+impl<'a, T: Real> TransformMut<'a, T> for &'a mut [T; 3] {
     /// Transforms the coordinates given in the OSS into a segment
     fn fro<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait,
     {
-        let v = self.to_owned().fro(segment);
-        let _ = std::mem::replace(self, v);
+        let v = (*self).fro(segment);
+        let _ = core::mem::replace(self, v);
     }
     /// Transforms a vector given in the OSS into a segment
     fn vfrov<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait,
     {
-        let v = self.to_owned().vfrov(segment);
-        let _ = std::mem::replace(self, v);
+        let v = (*self).vfrov(segment);
+        let _ = core::mem::replace(self, v);
     }
     /// Transforms the coordinates of a segment into the OSS
     fn to<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait,
     {
-        let v = self.to_owned().to(segment);
-        let _ = std::mem::replace(self, v);
+        let v = (*self).to(segment);
+        let _ = core::mem::replace(self, v);
     }
     /// Transforms a vector of a segment into the OSS
     fn vtov<M>(self, segment: Segment<M>)
     where
         M: Gmt,
-        Self: Into<Vector>,
+        Self: Into<Vector<T>>,
         Segment<M>: SegmentTrait,
     {
-        let v = self.to_owned().vtov(segment);
-        let _ = std::mem::replace(self, v);
+        let v = (*self).vtov(segment);
+        let _ = core::mem::replace(self, v);
     }
 }
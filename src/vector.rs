@@ -1,106 +1,130 @@
-use std::cmp::PartialEq;
-use std::fmt;
-use std::ops::{Add, AddAssign, Deref, DerefMut, Div, Mul, Neg, Sub};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cmp::PartialEq;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::{Add, AddAssign, Deref, DerefMut, Div, Mul, Neg, Sub};
+
+use crate::{Error, Real};
+
 /// Vector
 #[derive(Clone, Debug)]
-pub struct Vector([f64; 3]);
-impl Vector {
-    pub fn dot(&self, other: &Vector) -> f64 {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vector<T = f64>([T; 3]);
+impl<T: Real> Vector<T> {
+    pub fn dot(&self, other: &Vector<T>) -> T {
         self.0
             .iter()
             .zip(other.0.iter())
-            .fold(0., |a, (x, y)| a + x * y)
+            .fold(T::zero(), |a, (&x, &y)| a + x * y)
     }
-    pub fn cross(&self, other: &Vector) -> Vector {
+    pub fn cross(&self, other: &Vector<T>) -> Vector<T> {
         let [a1, a2, a3] = self.0;
         let [b1, b2, b3] = other.0;
         Vector([a2 * b3 - a3 * b2, a3 * b1 - a1 * b3, a1 * b2 - a2 * b1])
     }
-    pub fn norm_squared(&self) -> f64 {
+    pub fn norm_squared(&self) -> T {
         self.dot(self)
     }
-    pub fn norm(&self) -> f64 {
+    pub fn norm(&self) -> T {
         self.norm_squared().sqrt()
     }
     pub fn null() -> Self {
-        Vector::from(0f64)
+        Vector::from(T::zero())
     }
     pub fn i() -> Self {
-        Vector::from([1, 0, 0])
+        Vector([T::one(), T::zero(), T::zero()])
     }
     pub fn j() -> Self {
-        Vector::from([0, 1, 0])
+        Vector([T::zero(), T::one(), T::zero()])
     }
     pub fn k() -> Self {
-        Vector::from([0, 0, 1])
+        Vector([T::zero(), T::zero(), T::one()])
+    }
+    /// Converts the vector to another floating point type
+    pub fn cast<U: Real>(&self) -> Vector<U> {
+        Vector(self.0.map(|x| U::from_f64(x.into())))
     }
 }
-impl AsRef<[f64]> for Vector {
-    fn as_ref(&self) -> &[f64] {
+impl<T> AsRef<[T]> for Vector<T> {
+    fn as_ref(&self) -> &[T] {
         &self.0
     }
 }
-impl Deref for Vector {
-    type Target = [f64];
+impl<T> Deref for Vector<T> {
+    type Target = [T];
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
-impl DerefMut for Vector {
+impl<T> DerefMut for Vector<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
-impl From<Vec<f64>> for Vector {
-    fn from(v: Vec<f64>) -> Self {
-        Vector([v[0], v[1], v[2]])
+#[cfg(feature = "alloc")]
+impl<T: Real> TryFrom<Vec<T>> for Vector<T> {
+    type Error = Error;
+    /// Converts a 3 elements, or 2 elements with z=0, vector of finite values
+    fn try_from(v: Vec<T>) -> Result<Self, Self::Error> {
+        Vector::try_from(v.as_slice())
     }
 }
-impl From<f64> for Vector {
-    fn from(v: f64) -> Self {
+impl<T: Real> From<T> for Vector<T> {
+    fn from(v: T) -> Self {
         Vector([v; 3])
     }
 }
-impl From<std::slice::Iter<'_, f64>> for Vector {
-    fn from(v: std::slice::Iter<'_, f64>) -> Self {
-        Vector::from(v.cloned().collect::<Vec<f64>>())
+impl<T: Real> TryFrom<core::slice::Iter<'_, T>> for Vector<T> {
+    type Error = Error;
+    /// Converts a 3 elements, or 2 elements with z=0, slice iterator of finite values
+    fn try_from(v: core::slice::Iter<'_, T>) -> Result<Self, Self::Error> {
+        Vector::try_from(v.as_slice())
     }
 }
-impl From<[f64; 3]> for Vector {
-    fn from(v: [f64; 3]) -> Self {
+impl<T: Real> From<[T; 3]> for Vector<T> {
+    fn from(v: [T; 3]) -> Self {
         Vector(v)
     }
 }
-impl From<[i32; 3]> for Vector {
+impl<T: Real> From<[i32; 3]> for Vector<T> {
     fn from(v: [i32; 3]) -> Self {
-        Vector::from(v.iter().map(|&x| x as f64).collect::<Vec<f64>>())
+        Vector(v.map(|x| T::from_f64(x.into())))
     }
 }
-impl From<&[f64]> for Vector {
-    fn from(v: &[f64]) -> Self {
-        if v.len() == 2 {
-            Vector([v[0], v[1], 0.])
+impl<T: Real> TryFrom<&[T]> for Vector<T> {
+    type Error = Error;
+    /// Converts a 3 elements, or 2 elements with z=0, slice of finite values
+    fn try_from(v: &[T]) -> Result<Self, Self::Error> {
+        let u = match *v {
+            [x, y] => Vector([x, y, T::zero()]),
+            [x, y, z] => Vector([x, y, z]),
+            _ => return Err(Error::VectorLength(v.len())),
+        };
+        if u.0.iter().all(|x| x.is_finite()) {
+            Ok(u)
         } else {
-            Vector([v[0], v[1], v[2]])
+            Err(Error::NonFinite)
         }
     }
 }
-impl From<&mut [f64; 3]> for Vector {
-    fn from(v: &mut [f64; 3]) -> Self {
+impl<T: Real> From<&mut [T; 3]> for Vector<T> {
+    fn from(v: &mut [T; 3]) -> Self {
         Vector([v[0], v[1], v[2]])
     }
 }
-impl From<Vector> for Vec<f64> {
-    fn from(v: Vector) -> Self {
+#[cfg(feature = "alloc")]
+impl<T: Real> From<Vector<T>> for Vec<T> {
+    fn from(v: Vector<T>) -> Self {
         (*v).into()
     }
 }
-impl From<Vector> for [f64; 3] {
-    fn from(v: Vector) -> Self {
+impl<T> From<Vector<T>> for [T; 3] {
+    fn from(v: Vector<T>) -> Self {
         v.0
     }
 }
-impl Add for Vector {
+impl<T: Real> Add for Vector<T> {
     type Output = Self;
     fn add(self, other: Self) -> Self {
         Vector([
@@ -110,7 +134,7 @@ impl Add for Vector {
         ])
     }
 }
-impl AddAssign for Vector {
+impl<T: Real> AddAssign for Vector<T> {
     fn add_assign(&mut self, other: Self) {
         *self = Self([
             self.0[0] + other.0[0],
@@ -119,7 +143,7 @@ impl AddAssign for Vector {
         ])
     }
 }
-impl Add<&Vector> for Vector {
+impl<T: Real> Add<&Vector<T>> for Vector<T> {
     type Output = Self;
     fn add(self, other: &Self) -> Self {
         Vector([
@@ -129,7 +153,7 @@ impl Add<&Vector> for Vector {
         ])
     }
 }
-impl Sub for Vector {
+impl<T: Real> Sub for Vector<T> {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
         Vector([
@@ -139,7 +163,7 @@ impl Sub for Vector {
         ])
     }
 }
-impl Sub<&Vector> for Vector {
+impl<T: Real> Sub<&Vector<T>> for Vector<T> {
     type Output = Self;
     fn sub(self, other: &Self) -> Self {
         Vector([
@@ -149,15 +173,15 @@ impl Sub<&Vector> for Vector {
         ])
     }
 }
-impl Neg for Vector {
+impl<T: Real> Neg for Vector<T> {
     type Output = Self;
     fn neg(self) -> Self {
         Vector([-self.0[0], -self.0[1], -self.0[2]])
     }
 }
-impl Mul<f64> for &Vector {
-    type Output = Vector;
-    fn mul(self, rhs: f64) -> Vector {
+impl<T: Real> Mul<T> for &Vector<T> {
+    type Output = Vector<T>;
+    fn mul(self, rhs: T) -> Vector<T> {
         Vector([
             rhs * self.as_ref()[0],
             rhs * self.as_ref()[1],
@@ -165,9 +189,9 @@ impl Mul<f64> for &Vector {
         ])
     }
 }
-impl Div<f64> for &Vector {
-    type Output = Vector;
-    fn div(self, rhs: f64) -> Vector {
+impl<T: Real> Div<T> for &Vector<T> {
+    type Output = Vector<T>;
+    fn div(self, rhs: T) -> Vector<T> {
         Vector([
             self.as_ref()[0] / rhs,
             self.as_ref()[1] / rhs,
@@ -175,9 +199,9 @@ impl Div<f64> for &Vector {
         ])
     }
 }
-impl Div<f64> for Vector {
-    type Output = Vector;
-    fn div(self, rhs: f64) -> Vector {
+impl<T: Real> Div<T> for Vector<T> {
+    type Output = Vector<T>;
+    fn div(self, rhs: T) -> Vector<T> {
         Vector([
             self.as_ref()[0] / rhs,
             self.as_ref()[1] / rhs,
@@ -185,27 +209,34 @@ impl Div<f64> for Vector {
         ])
     }
 }
-impl Mul<&Vector> for f64 {
-    type Output = Vector;
-    fn mul(self, rhs: &Vector) -> Vector {
-        Vector([
-            rhs.as_ref()[0] * self,
-            rhs.as_ref()[1] * self,
-            rhs.as_ref()[2] * self,
-        ])
-    }
-}
-impl Mul<Vector> for f64 {
-    type Output = Vector;
-    fn mul(self, rhs: Vector) -> Vector {
-        Vector([
-            rhs.as_ref()[0] * self,
-            rhs.as_ref()[1] * self,
-            rhs.as_ref()[2] * self,
-        ])
-    }
+macro_rules! scalar_mul {
+    ($($t:ty),*) => {
+        $(
+            impl Mul<&Vector<$t>> for $t {
+                type Output = Vector<$t>;
+                fn mul(self, rhs: &Vector<$t>) -> Vector<$t> {
+                    Vector([
+                        rhs.as_ref()[0] * self,
+                        rhs.as_ref()[1] * self,
+                        rhs.as_ref()[2] * self,
+                    ])
+                }
+            }
+            impl Mul<Vector<$t>> for $t {
+                type Output = Vector<$t>;
+                fn mul(self, rhs: Vector<$t>) -> Vector<$t> {
+                    Vector([
+                        rhs.as_ref()[0] * self,
+                        rhs.as_ref()[1] * self,
+                        rhs.as_ref()[2] * self,
+                    ])
+                }
+            }
+        )*
+    };
 }
-impl PartialEq for Vector {
+scalar_mul!(f32, f64);
+impl<T: Real> PartialEq for Vector<T> {
     fn eq(&self, other: &Self) -> bool {
         self.0
             .iter()
@@ -213,7 +244,7 @@ impl PartialEq for Vector {
             .fold(true, |a, (x, y)| a && x == y)
     }
 }
-impl fmt::Display for Vector {
+impl<T: Real> fmt::Display for Vector<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
@@ -223,10 +254,10 @@ impl fmt::Display for Vector {
     }
 }
 
-impl IntoIterator for Vector {
-    type Item = f64;
+impl<T> IntoIterator for Vector<T> {
+    type Item = T;
 
-    type IntoIter = std::array::IntoIter<Self::Item, 3>;
+    type IntoIter = core::array::IntoIter<Self::Item, 3>;
 
     fn into_iter(self) -> Self::IntoIter {
         IntoIterator::into_iter(self.0)
@@ -259,4 +290,49 @@ mod tests {
         let s = 3. * u;
         assert_eq!(s, Vector::from([3., -6., 3.]));
     }
+
+    #[test]
+    fn vector_f32() {
+        let u = Vector::from([1f32, -2., 1.]);
+        let v = Vector::from([-1f32, 2., 3.]);
+        assert_eq!(u.cross(&v), Vector::from([-8f32, -4., 0.]));
+        assert_eq!(u.cast::<f64>(), Vector::from([1., -2., 1.]));
+        // out of range values saturate to infinity
+        let w = Vector::from([1e300, -1e300, f64::NAN]).cast::<f32>();
+        assert!(w[0] == f32::INFINITY && w[1] == f32::NEG_INFINITY && w[2].is_nan());
+    }
+
+    #[test]
+    fn vector_try_from() {
+        assert_eq!(
+            Vector::try_from(&[1., 2.][..]).unwrap(),
+            Vector::from([1., 2., 0.])
+        );
+        assert!(matches!(
+            Vector::try_from(&[1., f64::NAN, 0.][..]),
+            Err(Error::NonFinite)
+        ));
+        assert_eq!(
+            Vector::try_from([1., 2., 3.].iter()).unwrap(),
+            Vector::from([1., 2., 3.])
+        );
+        assert!(matches!(
+            Vector::try_from([1f64].iter()),
+            Err(Error::VectorLength(1))
+        ));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn vector_try_from_vec() {
+        use alloc::vec;
+        assert_eq!(
+            Vector::try_from(vec![1., 2., 3.]).unwrap(),
+            Vector::from([1., 2., 3.])
+        );
+        assert!(matches!(
+            Vector::try_from(vec![1f64]),
+            Err(Error::VectorLength(1))
+        ));
+    }
 }